@@ -1,163 +1,2005 @@
-use regex::Regex;
-use reqwest;
-use serde_json;
-use std::fs;
-use std::{io::Read, path::Path};
-use url::{Host::Domain, Url};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use toml::{Table, Value};
+use url::Url;
 
 use crate::utils::{
-    common::{get_default_chatterino_path, get_files_from_gzip},
-    github::handle_github_rate_limit,
-    plugin::{parse_plugins, print_plugin_info, print_plugins, write_plugin_data},
+    adopt::find_matching_commit,
+    audit::{audit_plugin_files, check_permissions, print_findings},
+    batch::{print_batch_results, run_batch, BatchResult},
+    cache::{
+        clean_cache, get_cached_responses_size, get_release_cache_file, list_cached_archives,
+        print_cached_archives, prune_cache,
+    },
+    common::{
+        extract_archive, format_size, get_cache_path, get_cpm_state_path, list_files,
+        parse_duration, prompt, read_archive, Plugin, PluginPermission,
+    },
+    config::{
+        flatten_config, get_config_path, get_config_value, parse_config_value, print_config,
+        read_config, set_config_value, unset_config_value, validate_config_key, write_config,
+    },
+    conflicts::{
+        find_conflicts, find_plugin_conflicts, print_conflicts, scan_installed_registrations,
+        scan_registrations, Registrations,
+    },
+    data::{
+        is_plugin_data_path, list_stashed_data, remove_stashed_data, stash_plugin_data,
+        PLUGIN_DATA_FOLDER,
+    },
+    doctor::{print_checks, run_checks},
+    filter::FileFilter,
+    github::{
+        download_release_archive, download_release_signature, download_tarball,
+        find_release_archive, get_commit_sha, get_default_branch, get_file_contents, get_release,
+        get_repo_info, parse_github_url, Release,
+    },
+    index::{
+        find_index_plugin, get_index, get_index_sources, get_indexes, print_index_plugins,
+        print_index_sources, remove_cached_index, search_indexes, validate_index_name, IndexSource,
+        LoadedIndex, DEFAULT_INDEX_NAME,
+    },
+    link::{
+        create_symlink, is_symlink, list_links, read_link, remove_link, remove_symlink,
+        sync_mirror, watch_mirror, write_link, Link,
+    },
+    lua::{check_lua_files, check_requires},
+    pack::{
+        check_pack_manifest, create_manifest, list_pack_files, write_sha256_sidecar, write_tar_gz,
+        write_zip, MANIFEST_FILE,
+    },
+    plan::{format_plan, is_dry_run, plan_install, plan_remove},
+    plugin::{
+        check_plugin_target, create_staging_folder, install_staged_plugin, parse_plugin,
+        parse_plugin_info, parse_plugins, print_plugin_info, print_plugins, print_stashed_data,
+        validate_plugin_folder, validate_plugin_info, PLUGIN_PERMISSION_TYPES,
+    },
+    progress::{print_line, Progress},
+    provenance::{
+        list_provenance, read_provenance, remove_provenance, write_provenance, Provenance,
+    },
+    runtime::{print_registrations, PluginRuntime},
+    scaffold::{create_plugin_files, write_plugin_files},
+    trust::{
+        check_release_signature, check_tarball_signature, get_trusted_keys, is_valid_repo,
+        parse_signature_policy, print_trusted_keys, set_trusted_key, TrustedKey,
+        SIGNATURE_EXTENSION, SIGNATURE_POLICY_KEY,
+    },
+    verify::{format_file_diff, hash_plugin_files, print_file_changes, verify_plugin_files},
 };
 
-use crate::VERSION_STR;
+struct PluginSource {
+    owner: String,
+    repo: String,
+    folder: String,
+    git_ref: Option<String>,
+    index_name: Option<String>,
+}
+
+/// Whether `cpm get` was given a GitHub repository rather than an index name
+fn is_repo_source(plugin: &str, is_repo: bool) -> bool {
+    is_repo || Url::parse(plugin).is_ok()
+}
+
+/// Resolve what `cpm get` was given to a GitHub repository, looking up names in the plugin index
+///
+/// ### Arguments
+/// * `plugin` GitHub repository URL or `[index/]name[@version]`
+/// * `is_repo` treat the plugin as a GitHub repository URL
+/// * `indexes` loaded plugin indexes, only used for index names
+fn resolve_plugin_source(
+    plugin: &str,
+    is_repo: bool,
+    indexes: &[LoadedIndex],
+) -> Result<PluginSource, String> {
+    if is_repo_source(plugin, is_repo) {
+        let (owner, repo) = parse_github_url(plugin)?;
+        return Ok(PluginSource {
+            folder: repo.clone(),
+            owner,
+            repo,
+            git_ref: None,
+            index_name: None,
+        });
+    }
+
+    let (name, version) = match plugin.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (plugin, None),
+    };
+
+    let found = find_index_plugin(indexes, name)?;
+    let index_version = found.plugin.find_version(version)?;
+
+    let (owner, repo) = parse_github_url(&found.plugin.source)?;
+    Ok(PluginSource {
+        owner,
+        repo,
+        folder: found.plugin.name.clone(),
+        git_ref: index_version.map(|v| v.git_ref()),
+        index_name: Some(found.qualified_name()),
+    })
+}
+
+/// How `get`, `update` and `reinstall` install a plugin
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    pub skip_lua_check: bool,
+    /// Install from the archive attached to a release instead of the repository's tarball
+    pub release: bool,
+    /// Patterns of files to leave out, in `.gitignore` syntax
+    pub exclude: Vec<String>,
+}
+
+/// Check a downloaded plugin's sources before installing it
+///
+/// Syntax errors stop the install, modules that can't be found only warn as they may be provided
+/// some other way. `cpm validate` reports both as errors.
+fn check_plugin_sources(
+    plugin_path: &Path,
+    folder: &str,
+    options: &InstallOptions,
+) -> Result<(), String> {
+    for problem in check_requires(plugin_path) {
+        print_line(&format!("Warning: {folder}: {problem}"));
+    }
+
+    if options.skip_lua_check {
+        return Ok(());
+    }
+
+    let errors = check_lua_files(plugin_path);
+    if !errors.is_empty() {
+        return Err(format!(
+            "{folder} has syntax errors in its Lua code, use --skip-lua-check to install it anyway:\n{}",
+            errors.join("\n")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Extract a downloaded plugin, leaving out the files its `.cpmignore`, the `files` in its
+/// `info.json` or `--exclude` leave out
+///
+/// Returns how many files were left out.
+fn extract_plugin_archive(
+    archive_path: &Path,
+    staging_path: &Path,
+    is_release: bool,
+    options: &InstallOptions,
+    progress: &mut Progress,
+) -> Result<usize, String> {
+    let filter = FileFilter::from_archive(archive_path, &options.exclude)?;
+    let archive = File::open(archive_path).or(Err(format!(
+        "There was an error reading {}",
+        archive_path.to_string_lossy()
+    )))?;
+
+    // the manifest of a release archive is needed to check it, and removed after
+    let left_out = extract_archive(
+        archive,
+        staging_path,
+        |file| (is_release && file == MANIFEST_FILE) || filter.is_kept(file),
+        progress,
+    )?;
+    if is_release {
+        check_pack_manifest(staging_path, &filter)?;
+    }
+
+    Ok(left_out)
+}
+
+/// Get the folder names and registrations of all installed plugins
+fn installed_registrations(chatterino_path: &Path) -> Result<Vec<(String, Registrations)>, String> {
+    let plugins_path = chatterino_path.join("Plugins");
+    let mut folders: Vec<String> = parse_plugins(&plugins_path)?
+        .into_iter()
+        .map(|p| p.folder)
+        .collect();
+    folders.sort();
+
+    Ok(scan_installed_registrations(&plugins_path, &folders))
+}
+
+/// Warn about commands a plugin registers that other installed plugins already register
+fn warn_command_conflicts(chatterino_path: &Path, plugin_path: &Path, folder: &str) {
+    let Ok(installed) = installed_registrations(chatterino_path) else {
+        return;
+    };
+
+    let registrations = scan_registrations(plugin_path);
+    for (command, owners) in find_plugin_conflicts(folder, &registrations, &installed) {
+        print_line(&format!(
+            "Warning: {folder} registers {command}, which is already registered by {}",
+            owners.join(", ")
+        ));
+    }
+}
+
+/// What a plugin is installed from
+enum PluginArchive<'a> {
+    /// The repository's tarball at a commit
+    Commit {
+        owner: &'a str,
+        repo: &'a str,
+        commit: &'a str,
+    },
+    /// An archive made with `cpm pack`, attached to a release
+    Release {
+        owner: &'a str,
+        repo: &'a str,
+        release: &'a Release,
+    },
+}
+
+/// Download the archive of a release and check its signature, before anything is extracted
+///
+/// Returns the path of the archive in the download cache.
+fn download_signed_release(
+    owner: &str,
+    repo: &str,
+    folder: &str,
+    release: &Release,
+    progress: &Progress,
+) -> Result<PathBuf, String> {
+    let archive = find_release_archive(release, folder)?;
+    let path = download_release_archive(owner, repo, release, archive, progress)?;
+    let signature = download_release_signature(owner, repo, release, archive, progress)?;
+    check_release_signature(owner, repo, &path, signature.as_deref(), &archive.name)?;
+
+    Ok(path)
+}
+
+/// Download a plugin and install it through a staging folder
+fn download_plugin(
+    chatterino_path: &Path,
+    folder: &str,
+    archive: &PluginArchive,
+    replace: bool,
+    options: &InstallOptions,
+) -> Result<(), String> {
+    let staging_path = create_staging_folder(chatterino_path, folder, replace)?;
+
+    let mut progress = Progress::new(folder);
+    let archive_path = match archive {
+        PluginArchive::Commit {
+            owner,
+            repo,
+            commit,
+        } => check_tarball_signature(owner, repo, folder)
+            .and_then(|_| download_tarball(owner, repo, commit, &progress)),
+        PluginArchive::Release {
+            owner,
+            repo,
+            release,
+        } => download_signed_release(owner, repo, folder, release, &progress),
+    };
+    let is_release = matches!(archive, PluginArchive::Release { .. });
+    let extracted = archive_path.and_then(|path| {
+        extract_plugin_archive(&path, &staging_path, is_release, options, &mut progress)
+    });
+
+    let left_out = match extracted {
+        Ok(left_out) => left_out,
+        Err(message) => {
+            progress.clear();
+            let _ = fs::remove_dir_all(&staging_path);
+            return Err(message);
+        }
+    };
+    progress.finish();
+
+    if left_out > 0 {
+        print_line(&format!("Left out {left_out} file(s) of {folder}"));
+    }
+    if let Err(message) = check_plugin_sources(&staging_path, folder, options) {
+        let _ = fs::remove_dir_all(&staging_path);
+        return Err(message);
+    }
+    warn_command_conflicts(chatterino_path, &staging_path, folder);
+
+    install_staged_plugin(chatterino_path, folder)
+}
+
+/// Read the files of a downloaded plugin that installing it would extract
+///
+/// ### Arguments
+/// * `archive_path` path to the archive
+/// * `is_release` whether it is a release archive, which has a manifest that isn't installed
+/// * `exclude` patterns given with `--exclude`
+fn read_plugin_archive(
+    archive_path: &Path,
+    is_release: bool,
+    exclude: &[String],
+) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let archive = File::open(archive_path).or(Err(format!(
+        "There was an error reading {}",
+        archive_path.to_string_lossy()
+    )))?;
+    let mut contents = read_archive(archive)?;
+    if is_release {
+        contents.remove(MANIFEST_FILE);
+    }
+
+    let filter = FileFilter::from_files(&contents, exclude)?;
+    contents.retain(|file, _| filter.is_kept(file));
+
+    Ok(contents)
+}
+
+/// Show what installing a plugin would change, without installing it
+///
+/// The archive is still downloaded into the download cache to list its files.
+fn plan_download(
+    chatterino_path: &Path,
+    folder: &str,
+    archive: &PluginArchive,
+    replace: bool,
+    heading: &str,
+    options: &InstallOptions,
+) -> Result<(), String> {
+    let plugin_path = check_plugin_target(chatterino_path, folder, replace)?;
+
+    let progress = Progress::new(folder);
+    let archive_path = match archive {
+        PluginArchive::Commit {
+            owner,
+            repo,
+            commit,
+        } => check_tarball_signature(owner, repo, folder)
+            .and_then(|_| download_tarball(owner, repo, commit, &progress)),
+        PluginArchive::Release {
+            owner,
+            repo,
+            release,
+        } => download_signed_release(owner, repo, folder, release, &progress),
+    };
+    let is_release = matches!(archive, PluginArchive::Release { .. });
+    let files = archive_path
+        .and_then(|path| read_plugin_archive(&path, is_release, &options.exclude))
+        .map(|contents| {
+            contents
+                .iter()
+                .map(|(file, c)| (file.clone(), c.len() as u64))
+                .collect::<Vec<_>>()
+        });
+    progress.clear();
+
+    let mut heading = heading.to_string();
+    let state_path = get_cpm_state_path(chatterino_path);
+    if plugin_path.join(PLUGIN_DATA_FOLDER).is_dir() {
+        heading.push_str(&format!("\nWould keep the data of {folder}"));
+    } else if list_stashed_data(&state_path)?
+        .iter()
+        .any(|d| d.folder == folder)
+    {
+        heading.push_str(&format!(
+            "\nWould restore data from a previous installation of {folder}"
+        ));
+    }
+
+    print_line(&format_plan(&heading, &plan_install(&plugin_path, files?)));
+
+    Ok(())
+}
+
+/// Shorten a commit SHA for output
+fn short_commit(commit: &str) -> String {
+    commit.chars().take(7).collect()
+}
+
+/// Report a batch of installs or updates, a single plugin is reported like any other command
+fn finish_batch(results: Vec<BatchResult>) -> Result<(), String> {
+    match results.as_slice() {
+        [BatchResult {
+            result: Some(result),
+            ..
+        }] => result.clone().map(|_| ()),
+        _ => print_batch_results(&results),
+    }
+}
+
+/// Install one plugin, returning what was installed
+fn install_plugin(
+    plugin: &str,
+    is_repo: bool,
+    chatterino_path: &Path,
+    indexes: &[LoadedIndex],
+    options: &InstallOptions,
+) -> Result<String, String> {
+    let source = resolve_plugin_source(plugin, is_repo, indexes)?;
+
+    // a version or ref picks the release with that tag
+    let release = if options.release {
+        Some(get_release(
+            &source.owner,
+            &source.repo,
+            source.git_ref.as_deref(),
+        )?)
+    } else {
+        None
+    };
+
+    // resolve the ref to a commit so updates can tell what changed
+    let git_ref = match (&release, source.git_ref) {
+        (Some(release), _) => release.tag.clone(),
+        (None, Some(git_ref)) => git_ref,
+        (None, None) => get_default_branch(&source.owner, &source.repo)?,
+    };
+    let commit = get_commit_sha(&source.owner, &source.repo, &git_ref)?;
+    let archive = match &release {
+        Some(release) => PluginArchive::Release {
+            owner: &source.owner,
+            repo: &source.repo,
+            release,
+        },
+        None => PluginArchive::Commit {
+            owner: &source.owner,
+            repo: &source.repo,
+            commit: &commit,
+        },
+    };
+
+    if is_dry_run() {
+        let summary = format!(
+            "Would install {} from {}/{} at {git_ref} ({})",
+            source.folder,
+            source.owner,
+            source.repo,
+            short_commit(&commit)
+        );
+        plan_download(
+            chatterino_path,
+            &source.folder,
+            &archive,
+            false,
+            &format!(
+                "{summary} into {}",
+                chatterino_path
+                    .join("Plugins")
+                    .join(&source.folder)
+                    .to_string_lossy()
+            ),
+            options,
+        )?;
+        return Ok(summary);
+    }
+
+    // write to plugin folder
+    download_plugin(chatterino_path, &source.folder, &archive, false, options)?;
+
+    let mut provenance = Provenance::new(&source.owner, &source.repo, &git_ref, &commit);
+    provenance.index_name = source.index_name;
+    provenance.exclude = options.exclude.clone();
+    provenance.asset = release
+        .map(|r| find_release_archive(&r, &source.folder).map(|a| a.name.clone()))
+        .transpose()?;
+    provenance.files = hash_plugin_files(&chatterino_path.join("Plugins").join(&source.folder))?;
+    write_provenance(
+        &get_cpm_state_path(chatterino_path),
+        &source.folder,
+        &provenance,
+    )?;
+
+    Ok(format!(
+        "Installed {} at {git_ref} ({})",
+        source.folder,
+        short_commit(&commit)
+    ))
+}
 
-pub fn get_plugin(
-    plugin: &String,
+/// Check that no two plugins would be installed into the same folder, e.g. a repository URL and
+/// its index name
+///
+/// Plugins that can't be resolved are left for their install to report.
+fn check_duplicate_folders(
+    plugins: &[String],
     is_repo: bool,
-    chatterino_path: Option<&String>,
+    indexes: &[LoadedIndex],
 ) -> Result<(), String> {
-    if !is_repo {
-        return Err("Non repo plugins are not currently supported!".to_string());
+    let mut folders: HashMap<String, &str> = HashMap::new();
+
+    for plugin in plugins {
+        let Ok(source) = resolve_plugin_source(plugin, is_repo, indexes) else {
+            continue;
+        };
+
+        // folder names are case-insensitive on Windows and macOS
+        if let Some(other) = folders.insert(source.folder.to_lowercase(), plugin) {
+            return Err(format!(
+                "{other} and {plugin} would both be installed into Plugins/{}",
+                source.folder
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn get_plugins(
+    plugins: Vec<String>,
+    is_repo: bool,
+    chatterino_path: &Path,
+    jobs: usize,
+    fail_fast: bool,
+    options: &InstallOptions,
+) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    let plugins: Vec<String> = plugins
+        .into_iter()
+        .filter(|p| seen.insert(p.clone()))
+        .collect();
+
+    // load the indexes once instead of in every worker
+    let indexes = if plugins.iter().all(|p| is_repo_source(p, is_repo)) {
+        Vec::new()
+    } else {
+        get_indexes()?
+    };
+
+    check_duplicate_folders(&plugins, is_repo, &indexes)?;
+
+    let results = run_batch(&plugins, jobs, fail_fast, |plugin| {
+        install_plugin(plugin, is_repo, chatterino_path, &indexes, options)
+    });
+
+    finish_batch(results)
+}
+
+/// Get the options to install a plugin again with, `--exclude` defaults to what it was installed
+/// with
+fn installed_options(options: &InstallOptions, provenance: &Provenance) -> InstallOptions {
+    let mut options = options.clone();
+    if options.exclude.is_empty() {
+        options.exclude = provenance.exclude.clone();
     }
 
-    // parse url
-    let parsed_url = Url::parse(plugin).or(Err("Invalid URL".to_string()))?;
+    options
+}
+
+/// Update one plugin installed by cpm, returning what changed
+fn update_plugin(
+    chatterino_path: &Path,
+    folder: &str,
+    provenance: Provenance,
+    indexes: &[LoadedIndex],
+    options: &InstallOptions,
+) -> Result<String, String> {
+    let options = &installed_options(options, &provenance);
+
+    // plugins installed from a release follow the latest release
+    let release = match provenance.asset {
+        Some(_) => Some(get_release(&provenance.owner, &provenance.repo, None)?),
+        None => None,
+    };
+
+    // plugins from the index follow its newest version rather than a branch
+    let git_ref = match (&release, &provenance.index_name) {
+        (Some(release), _) => release.tag.clone(),
+        (None, Some(index_name)) => find_index_plugin(indexes, index_name)?
+            .plugin
+            .latest_version()
+            .map(|v| v.git_ref())
+            .unwrap_or(provenance.ref_.clone()),
+        (None, None) => provenance.ref_.clone(),
+    };
 
-    // check if domain is github.com
-    let domain = parsed_url.host().ok_or("Could not parse domain")?;
-    if domain != Domain("github.com") {
-        return Err("Invalid GitHub repository URL".to_string());
+    let commit = get_commit_sha(&provenance.owner, &provenance.repo, &git_ref)?;
+    if commit == provenance.commit {
+        return Ok("Already up to date".to_string());
     }
+    let archive = match &release {
+        Some(release) => PluginArchive::Release {
+            owner: &provenance.owner,
+            repo: &provenance.repo,
+            release,
+        },
+        None => PluginArchive::Commit {
+            owner: &provenance.owner,
+            repo: &provenance.repo,
+            commit: &commit,
+        },
+    };
 
-    // extract owner and repo name from path
-    let github_path_re = Regex::new(r"^/([^/]+)/([^/]+)/?$").unwrap();
-    let captures = github_path_re
-        .captures(parsed_url.path())
-        .ok_or("Invalid GitHub repository URL")?;
+    if is_dry_run() {
+        let summary = format!(
+            "Would update {} to {}",
+            short_commit(&provenance.commit),
+            short_commit(&commit)
+        );
+        plan_download(
+            chatterino_path,
+            folder,
+            &archive,
+            true,
+            &format!(
+                "Would update {folder} from {} to {git_ref} ({})",
+                short_commit(&provenance.commit),
+                short_commit(&commit)
+            ),
+            options,
+        )?;
+        return Ok(summary);
+    }
 
-    let owner = captures
-        .get(1)
-        .ok_or("Could not parse repository owner from URL")?
-        .as_str();
+    download_plugin(chatterino_path, folder, &archive, true, options)?;
 
-    let repo = captures
-        .get(2)
-        .ok_or("Could not parse repository name from URL")?
-        .as_str();
+    let mut updated_provenance =
+        Provenance::new(&provenance.owner, &provenance.repo, &git_ref, &commit);
+    updated_provenance.index_name = provenance.index_name;
+    updated_provenance.exclude = options.exclude.clone();
+    updated_provenance.asset = release
+        .map(|r| find_release_archive(&r, folder).map(|a| a.name.clone()))
+        .transpose()?;
+    updated_provenance.files = hash_plugin_files(&chatterino_path.join("Plugins").join(folder))?;
+    write_provenance(
+        &get_cpm_state_path(chatterino_path),
+        folder,
+        &updated_provenance,
+    )?;
 
-    // github api to get default branch
-    let repo_info_url = format!("https://api.github.com/repos/{owner}/{repo}");
-    let client = reqwest::blocking::Client::new();
+    Ok(format!(
+        "Updated {} to {}",
+        short_commit(&provenance.commit),
+        short_commit(&commit)
+    ))
+}
 
-    let request = client
-        .get(repo_info_url)
-        .header(
-            "User-Agent",
-            format!("Chatterino Plugin Manager {VERSION_STR}"),
+pub fn update_plugins(
+    chatterino_path: &Path,
+    plugin_names: Vec<String>,
+    jobs: usize,
+    fail_fast: bool,
+    options: &InstallOptions,
+) -> Result<(), String> {
+    // get chatterino plugins folder path
+    let chatterino_plugins_path = chatterino_path.join("Plugins");
+    let state_path = get_cpm_state_path(chatterino_path);
+
+    let plugins = parse_plugins(&chatterino_plugins_path)?;
+    let folders: Vec<String> = if plugin_names.is_empty() {
+        plugins.iter().map(|p| p.folder.clone()).collect()
+    } else {
+        let mut folders = Vec::new();
+        for plugin_name in &plugin_names {
+            let plugin = plugins
+                .iter()
+                .find(|p| &p.folder == plugin_name)
+                .ok_or(format!("Plugin '{plugin_name}' not found."))?;
+            folders.push(plugin.folder.clone());
+        }
+        folders
+    };
+
+    let mut installed = Vec::new();
+    for folder in folders {
+        let Some(provenance) = read_provenance(&state_path, &folder)? else {
+            if !plugin_names.is_empty() {
+                return Err(format!(
+                    "Plugin '{folder}' was not installed by cpm and cannot be updated."
+                ));
+            }
+            println!("Skipped {folder}, it was not installed by cpm");
+            continue;
+        };
+        installed.push((folder, provenance));
+    }
+
+    // load the indexes once instead of in every worker
+    let indexes = if installed.iter().any(|(_, p)| p.index_name.is_some()) {
+        get_indexes()?
+    } else {
+        Vec::new()
+    };
+
+    let folders: Vec<String> = installed.iter().map(|(f, _)| f.clone()).collect();
+    let results = run_batch(&folders, jobs, fail_fast, |folder| {
+        let (_, provenance) = installed.iter().find(|(f, _)| f == folder).unwrap();
+        update_plugin(
+            chatterino_path,
+            folder,
+            provenance.clone(),
+            &indexes,
+            options,
         )
-        .header("Accept", "application/json");
+    });
 
-    let response = request.send().or(Err(
-        "There was en error getting GitHub repository info".to_string()
+    if let [BatchResult {
+        name,
+        result: Some(Ok(details)),
+    }] = results.as_slice()
+    {
+        println!("{name}: {details}");
+    }
+
+    finish_batch(results)
+}
+
+pub fn reinstall_plugin(
+    chatterino_path: &Path,
+    plugin_name: &str,
+    options: &InstallOptions,
+) -> Result<(), String> {
+    let plugin_path = chatterino_path.join("Plugins").join(plugin_name);
+    let state_path = get_cpm_state_path(chatterino_path);
+
+    let provenance = read_provenance(&state_path, plugin_name)?.ok_or(format!(
+        "Plugin '{plugin_name}' was not installed by cpm and cannot be reinstalled."
     ))?;
-    handle_github_rate_limit(&response)?;
+    let options = &installed_options(options, &provenance);
 
-    // parse response body as json and get `default_branch`
-    let default_branch: String = if let Ok(json) = response.json::<serde_json::Value>() {
-        serde_json::from_value(json.get("default_branch").unwrap().to_owned()).unwrap()
-    } else {
-        return Err("There was an error parsing the GitHub API response".to_string());
+    let release = match provenance.asset {
+        Some(_) => Some(get_release(
+            &provenance.owner,
+            &provenance.repo,
+            Some(&provenance.ref_),
+        )?),
+        None => None,
     };
+    let archive = match &release {
+        Some(release) => PluginArchive::Release {
+            owner: &provenance.owner,
+            repo: &provenance.repo,
+            release,
+        },
+        None => PluginArchive::Commit {
+            owner: &provenance.owner,
+            repo: &provenance.repo,
+            commit: &provenance.commit,
+        },
+    };
+
+    if is_dry_run() {
+        return plan_download(
+            chatterino_path,
+            plugin_name,
+            &archive,
+            true,
+            &format!(
+                "Would reinstall {plugin_name} from {}/{} at {} ({})",
+                provenance.owner,
+                provenance.repo,
+                provenance.ref_,
+                short_commit(&provenance.commit)
+            ),
+            options,
+        );
+    }
+
+    // tell what is being repaired, the whole plugin is replaced either way
+    if plugin_path.is_dir() && !provenance.files.is_empty() {
+        let changes = verify_plugin_files(&plugin_path, &provenance.files)?;
+        if !changes.is_empty() {
+            println!("Repairing {} changed file(s)", changes.len());
+            print_file_changes(&changes);
+        }
+    }
+
+    download_plugin(chatterino_path, plugin_name, &archive, true, options)?;
 
-    // get tarball
-    let repo_tarball_url =
-        format!("https://api.github.com/repos/{owner}/{repo}/tarball/{default_branch}");
-    let client = reqwest::blocking::Client::new();
+    let mut reinstalled_provenance = Provenance::new(
+        &provenance.owner,
+        &provenance.repo,
+        &provenance.ref_,
+        &provenance.commit,
+    );
+    reinstalled_provenance.index_name = provenance.index_name;
+    reinstalled_provenance.exclude = options.exclude.clone();
+    reinstalled_provenance.asset = provenance.asset;
+    reinstalled_provenance.files = hash_plugin_files(&plugin_path)?;
+    write_provenance(&state_path, plugin_name, &reinstalled_provenance)?;
 
-    let request = client.get(repo_tarball_url).header(
-        "User-Agent",
-        format!("Chatterino Plugin Manager {VERSION_STR}"),
+    println!(
+        "Reinstalled {plugin_name} at {} ({})",
+        provenance.ref_,
+        short_commit(&provenance.commit)
     );
 
-    let mut response = request.send().or(Err(
-        "There was en error downloading GitHub repository tarball".to_string(),
+    Ok(())
+}
+
+pub fn adopt_plugin(
+    chatterino_path: &Path,
+    plugin_name: &str,
+    source: Option<&String>,
+) -> Result<(), String> {
+    let plugin_path = chatterino_path.join("Plugins").join(plugin_name);
+    let state_path = get_cpm_state_path(chatterino_path);
+
+    let plugin = parse_plugin(plugin_path.clone(), plugin_name.to_string())?
+        .ok_or(format!("Plugin '{plugin_name}' not found."))?;
+    if read_provenance(&state_path, plugin_name)?.is_some() {
+        return Err(format!("Plugin '{plugin_name}' is already managed by cpm."));
+    }
+
+    // plugins usually link their repository as their homepage
+    let source = source.cloned().or(plugin.homepage).ok_or(format!(
+        "Could not tell where {plugin_name} came from, use --source <url>"
     ))?;
-    handle_github_rate_limit(&response)?;
+    let (owner, repo) = parse_github_url(&source).or(Err(format!(
+        "{source} is not a GitHub repository, use --source <url>"
+    )))?;
 
-    // write tarball to vec
-    let mut buf: Vec<u8> = vec![];
-    response
-        .read_to_end(&mut buf)
-        .or(Err("There was an writing the tarball".to_string()))?;
+    let matched = find_matching_commit(&owner, &repo, &plugin_path)?;
+    let found = match &matched.tag {
+        Some(tag) => format!("{} (tag {tag})", short_commit(&matched.commit)),
+        None => short_commit(&matched.commit),
+    };
+
+    if is_dry_run() {
+        println!("Would adopt {plugin_name} as {owner}/{repo} at {found}");
+        return Ok(());
+    }
 
-    let files = get_files_from_gzip(&buf);
+    let mut provenance = Provenance::new(&owner, &repo, &matched.git_ref, &matched.commit);
+    provenance.files = hash_plugin_files(&plugin_path)?;
+    write_provenance(&state_path, plugin_name, &provenance)?;
 
-    // get chatterino plugins folder path
-    let chatterino_plugins_path = if let Some(chatterino_path) = chatterino_path {
-        Path::new(chatterino_path).to_owned().join("Plugins")
+    println!(
+        "Adopted {plugin_name} as {owner}/{repo} at {found}, following {}",
+        matched.git_ref
+    );
+
+    Ok(())
+}
+
+/// Find a plugin folder from a path or the name of an installed plugin
+///
+/// ### Arguments
+/// * `plugin` path to a plugin folder or name of an installed plugin
+/// * `chatterino_path` resolves the Chatterino folder, only if `plugin` isn't a folder
+fn find_plugin_folder(
+    plugin: &str,
+    chatterino_path: impl Fn() -> Result<PathBuf, String>,
+) -> Result<PathBuf, String> {
+    let path = PathBuf::from(plugin);
+    if path.is_dir() {
+        return fs::canonicalize(&path).or(Err(format!("Could not read {plugin}")));
+    }
+
+    let installed_path = chatterino_path()?.join("Plugins").join(plugin);
+    if installed_path.is_dir() {
+        return Ok(installed_path);
+    }
+
+    Err(format!("Plugin or folder '{plugin}' not found."))
+}
+
+pub fn validate_plugin(
+    plugin: &str,
+    chatterino_path: impl Fn() -> Result<PathBuf, String>,
+) -> Result<(), String> {
+    let plugin_path = find_plugin_folder(plugin, chatterino_path)?;
+
+    let problems = validate_plugin_folder(&plugin_path);
+    if problems.is_empty() {
+        println!("{} looks good", plugin_path.to_string_lossy());
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+    Err(format!("Found {} problem(s)", problems.len()))
+}
+
+pub fn audit_plugin(
+    plugin: &str,
+    chatterino_path: impl Fn() -> Result<PathBuf, String>,
+) -> Result<(), String> {
+    let plugin_path = find_plugin_folder(plugin, chatterino_path)?;
+    let folder = plugin_path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or(plugin.to_string());
+
+    let declared: Vec<String> = parse_plugin(plugin_path.clone(), folder.clone())?
+        .ok_or(format!("{folder} has no info.json"))?
+        .permissions
+        .into_iter()
+        .map(|p| p.type_)
+        .collect();
+
+    let findings = audit_plugin_files(&plugin_path);
+    if findings.is_empty() {
+        println!("{folder} doesn't use files, the network or unavailable libraries");
     } else {
-        get_default_chatterino_path()?.join("Plugins")
-    };
+        print_findings(&findings, &declared);
+    }
 
-    // write to plugin folder
-    write_plugin_data(chatterino_plugins_path, repo, files)?;
+    let problems = check_permissions(&findings, &declared);
+    for permission in &problems.unused {
+        println!("Declares {permission} but never uses it");
+    }
+    for permission in &problems.missing {
+        println!("Uses {permission} without declaring it in info.json");
+    }
+
+    if !problems.missing.is_empty() {
+        return Err(format!(
+            "{folder} needs {} permission(s) it doesn't declare",
+            problems.missing.len()
+        ));
+    }
 
     Ok(())
 }
 
-pub fn list_plugins(chatterino_path: Option<&String>) -> Result<(), String> {
-    // get chatterino plugins folder path
-    let chatterino_plugins_path = if let Some(chatterino_path) = chatterino_path {
-        Path::new(chatterino_path).to_owned().join("Plugins")
+pub fn test_plugin(
+    plugin: &str,
+    chatterino_path: impl Fn() -> Result<PathBuf, String>,
+    commands: &[String],
+    channel: &str,
+) -> Result<(), String> {
+    let plugin_path = find_plugin_folder(plugin, chatterino_path)?;
+    let folder = plugin_path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or(plugin.to_string());
+
+    let permissions = parse_plugin(plugin_path.clone(), folder.clone())?
+        .map(|p| p.permissions)
+        .unwrap_or_default();
+    let runtime = PluginRuntime::load(&plugin_path, &permissions)
+        .map_err(|e| format!("{folder} raised an error while loading:\n{e}"))?;
+
+    let registered_commands = runtime.commands();
+    let callbacks = runtime.callbacks();
+    if registered_commands.is_empty() && callbacks.is_empty() {
+        println!("{folder} loaded without registering any commands or callbacks");
     } else {
-        get_default_chatterino_path()?.join("Plugins")
+        println!("{folder} loaded and registered:");
+        print_registrations(&registered_commands, &callbacks);
+    }
+
+    let mut failed = 0;
+    for command in commands {
+        println!("> {command}");
+        if let Err(e) = runtime.run_command(command, channel) {
+            println!("  Error: {e}");
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{failed} command(s) raised an error"));
+    }
+
+    Ok(())
+}
+
+/// Split a comma separated answer into its items
+fn split_list(answer: &str) -> Vec<String> {
+    answer
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Ask for the permissions of a new plugin until only known ones are given
+fn ask_permissions() -> Result<Vec<PluginPermission>, String> {
+    loop {
+        let answer = prompt(
+            &format!(
+                "Permissions (comma separated: {})",
+                PLUGIN_PERMISSION_TYPES.join(", ")
+            ),
+            "",
+        )?;
+
+        let types = split_list(&answer);
+        match types
+            .iter()
+            .find(|t| !PLUGIN_PERMISSION_TYPES.contains(&t.as_str()))
+        {
+            Some(unknown) => println!("Unknown permission '{unknown}'"),
+            None => {
+                return Ok(types
+                    .into_iter()
+                    .map(|type_| PluginPermission { type_ })
+                    .collect())
+            }
+        }
+    }
+}
+
+/// Create a new plugin from a template
+///
+/// ### Arguments
+/// * `name` folder name of the plugin
+/// * `chatterino_path` path to Chatterino folder to create the plugin in `Plugins/`, or `None` to
+///   create it in the current folder
+/// * `plugin` metadata given on the command line, anything missing is asked for
+/// * `ask` whether to ask for missing metadata
+pub fn new_plugin(
+    name: &str,
+    chatterino_path: Option<&Path>,
+    mut plugin: Plugin,
+    ask: bool,
+) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(format!("'{name}' is not a valid plugin folder name"));
+    }
+
+    let plugin_path = match chatterino_path {
+        Some(chatterino_path) => check_plugin_target(chatterino_path, name, false)?,
+        None => PathBuf::from(name),
     };
+    if plugin_path.exists() {
+        return Err(format!("{} already exists", plugin_path.to_string_lossy()));
+    }
 
-    let plugins = parse_plugins(&chatterino_plugins_path)?;
-    print_plugins(plugins);
+    plugin.folder = name.to_string();
+    if ask {
+        if plugin.description.is_none() {
+            plugin.description = Some(prompt("Description", "")?);
+        }
+        if plugin.authors.is_empty() {
+            plugin.authors = split_list(&prompt("Authors (comma separated)", "")?);
+        }
+        if plugin.licence.is_none() {
+            plugin.licence = Some(prompt("Licence", "MIT")?);
+        }
+        if plugin.tags.is_empty() {
+            plugin.tags = split_list(&prompt("Tags (comma separated)", "")?);
+        }
+        if plugin.permissions.is_empty() {
+            plugin.permissions = ask_permissions()?;
+        }
+    }
+
+    let files = create_plugin_files(&plugin);
+    if is_dry_run() {
+        let sizes = files
+            .iter()
+            .map(|(file, contents)| (file.clone(), contents.len() as u64))
+            .collect();
+        print_line(&format_plan(
+            &format!("Would create {}", plugin_path.to_string_lossy()),
+            &plan_install(&plugin_path, sizes),
+        ));
+        return Ok(());
+    }
+
+    write_plugin_files(&plugin_path, &files)?;
+    println!("Created {}", plugin_path.to_string_lossy());
 
     Ok(())
 }
 
-pub fn remove_plugin(chatterino_path: Option<&String>, plugin_name: String) -> Result<(), String> {
+pub fn pack_plugin(
+    plugin: &str,
+    chatterino_path: impl Fn() -> Result<PathBuf, String>,
+    output: Option<&String>,
+    zip: bool,
+) -> Result<(), String> {
+    let plugin_path = find_plugin_folder(plugin, chatterino_path)?;
+    let folder = plugin_path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or(plugin.to_string());
+
+    let problems = validate_plugin_folder(&plugin_path);
+    if !problems.is_empty() {
+        return Err(format!(
+            "{folder} has problems, fix them before packing it:\n{}",
+            problems.join("\n")
+        ));
+    }
+
+    let info = parse_plugin(plugin_path.clone(), folder.clone())?
+        .ok_or(format!("{folder} has no info.json"))?;
+    let version = info
+        .version
+        .ok_or(format!("{folder} has no version in its info.json"))?;
+    let name = info.name.unwrap_or(folder.clone());
+
+    let output_path = match output {
+        Some(output) => PathBuf::from(output),
+        None => env::current_dir().or(Err("Could not get the current folder"))?,
+    };
+    fs::create_dir_all(&output_path).or(Err(format!(
+        "Could not create {}",
+        output_path.to_string_lossy()
+    )))?;
+    let output_path = fs::canonicalize(&output_path).or(Err("Could not read the output folder"))?;
+
+    // packing into the plugin folder must not pack earlier packs
+    let skip = output_path
+        .strip_prefix(&plugin_path)
+        .ok()
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .filter(|p| !p.is_empty());
+    let filter = FileFilter::read(&plugin_path, &[])?;
+    let files = list_pack_files(&plugin_path, &filter, skip.as_deref());
+    let manifest = create_manifest(&plugin_path, &files, &name, &folder, &version)?;
+
+    let mut archives = vec![output_path.join(format!("{folder}-{version}.tar.gz"))];
+    if zip {
+        archives.push(output_path.join(format!("{folder}-{version}.zip")));
+    }
+
+    if is_dry_run() {
+        println!("Would pack {} file(s) of {folder}:", files.len());
+        for file in &files {
+            println!("  {file}");
+        }
+        for archive in &archives {
+            println!("Would write {}", archive.to_string_lossy());
+        }
+        return Ok(());
+    }
+
+    for archive in &archives {
+        if archive.extension().is_some_and(|e| e == "zip") {
+            write_zip(&plugin_path, &manifest, archive)?;
+        } else {
+            write_tar_gz(&plugin_path, &manifest, archive)?;
+        }
+        let sha256 = write_sha256_sidecar(archive)?;
+        println!(
+            "Packed {} file(s) into {} (sha256 {sha256})",
+            files.len(),
+            archive.to_string_lossy()
+        );
+    }
+    println!(
+        "Sign it with `minisign -Sm {}` and upload the .{SIGNATURE_EXTENSION} next to it",
+        archives[0].to_string_lossy()
+    );
+
+    Ok(())
+}
+
+pub fn list_plugins(chatterino_path: &Path, conflicts: bool) -> Result<(), String> {
+    if conflicts {
+        return list_conflicts(chatterino_path);
+    }
+
     // get chatterino plugins folder path
-    let chatterino_plugins_path = if let Some(chatterino_path) = chatterino_path {
-        Path::new(chatterino_path).to_owned().join("Plugins")
+    let chatterino_plugins_path = chatterino_path.join("Plugins");
+
+    let plugins = parse_plugins(&chatterino_plugins_path)?;
+    let links = list_links(&get_cpm_state_path(chatterino_path));
+    print_plugins(plugins, &links);
+
+    Ok(())
+}
+
+fn list_conflicts(chatterino_path: &Path) -> Result<(), String> {
+    let conflicts = find_conflicts(&installed_registrations(chatterino_path)?);
+    if conflicts.is_empty() {
+        println!("No installed plugins register the same command");
+        return Ok(());
+    }
+
+    print_conflicts(&conflicts);
+
+    Ok(())
+}
+
+pub fn link_plugin(
+    chatterino_path: &Path,
+    source: &str,
+    name: Option<&String>,
+    watch: bool,
+) -> Result<(), String> {
+    let source_path = fs::canonicalize(source)
+        .ok()
+        .filter(|p| p.is_dir())
+        .ok_or(format!("Folder '{source}' not found."))?;
+    if !source_path.join("info.json").is_file() {
+        return Err(format!("{source} has no info.json"));
+    }
+
+    let folder = match name {
+        Some(name) => name.clone(),
+        None => source_path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .ok_or(format!(
+                "Could not get a folder name for {source}, use --name"
+            ))?,
+    };
+    let state_path = get_cpm_state_path(chatterino_path);
+    let plugin_path = chatterino_path.join("Plugins").join(&folder);
+    let source_str = source_path.to_string_lossy();
+
+    // watching an existing mirror again picks up where the last watch stopped
+    let is_existing_mirror = read_link(&state_path, &folder)?
+        .is_some_and(|link| link.mirror && link.source == source_path);
+    if !is_existing_mirror {
+        check_plugin_target(chatterino_path, &folder, false)?;
+    }
+
+    // a link whose working copy was moved or deleted is replaced
+    let is_broken_link = is_symlink(&plugin_path)
+        && !plugin_path.exists()
+        && list_links(&state_path).contains_key(&folder);
+
+    if is_dry_run() {
+        match (watch, is_existing_mirror) {
+            (true, true) => println!("Would sync {folder} with {source_str} and watch it"),
+            (true, false) => println!(
+                "Would mirror {source_str} to {} and watch it",
+                plugin_path.to_string_lossy()
+            ),
+            (false, _) => println!(
+                "Would link {} to {source_str}",
+                plugin_path.to_string_lossy()
+            ),
+        }
+        return Ok(());
+    }
+
+    if is_broken_link {
+        remove_symlink(&plugin_path).or(Err(format!(
+            "There was an error removing the broken link of {folder}"
+        )))?;
+        remove_link(&state_path, &folder)?;
+    }
+
+    if !watch {
+        if is_existing_mirror {
+            return Err(format!(
+                "{folder} is already a mirror of {source_str}, use --watch to sync it"
+            ));
+        }
+
+        create_symlink(&source_path, &plugin_path)?;
+        write_link(&state_path, &folder, &Link::new(&source_path, false))?;
+        println!("Linked {} to {source_str}", plugin_path.to_string_lossy());
+        return Ok(());
+    }
+
+    let changed = sync_mirror(&source_path, &plugin_path)?;
+    if !is_existing_mirror {
+        write_link(&state_path, &folder, &Link::new(&source_path, true))?;
+        println!("Mirrored {source_str} to {}", plugin_path.to_string_lossy());
+    } else if changed > 0 {
+        println!("Synced {changed} file(s)");
+    }
+
+    watch_mirror(&source_path, &plugin_path)
+}
+
+/// Remove a symlinked plugin without touching the folder it links to
+fn unlink_plugin(state_path: &Path, plugin_path: &Path, folder: &str) -> Result<(), String> {
+    let source = fs::read_link(plugin_path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or("its source".to_string());
+    let outcome = if plugin_path.exists() {
+        format!("{source} is kept")
     } else {
-        get_default_chatterino_path()?.join("Plugins")
+        format!("{source} no longer exists")
     };
 
+    if is_dry_run() {
+        println!("Would unlink {folder}, {outcome}");
+        return Ok(());
+    }
+
+    remove_symlink(plugin_path).or(Err(format!("There was an error unlinking {folder}")))?;
+    remove_link(state_path, folder)?;
+
+    println!("Unlinked {folder}, {outcome}");
+
+    Ok(())
+}
+
+pub fn remove_plugin(
+    chatterino_path: &Path,
+    plugin_name: String,
+    purge: bool,
+) -> Result<(), String> {
+    // get chatterino plugins folder path
+    let chatterino_plugins_path = chatterino_path.join("Plugins");
+    let state_path = get_cpm_state_path(chatterino_path);
+
+    // a linked plugin whose working copy is gone has no info.json to be found by
+    let link_path = chatterino_plugins_path.join(&plugin_name);
+    if is_symlink(&link_path) && list_links(&state_path).contains_key(&plugin_name) {
+        return unlink_plugin(&state_path, &link_path, &plugin_name);
+    }
+
     let plugins = parse_plugins(&chatterino_plugins_path)?;
     let plugin = plugins
         .iter()
         .find(|p| p.folder == plugin_name)
         .ok_or(format!("Plugin '{plugin_name}' not found."))?;
 
-    fs::remove_dir_all(chatterino_plugins_path.join(&plugin.folder))
-        .or(Err("There was an error removing the plugin"))?;
+    let plugin_path = chatterino_plugins_path.join(&plugin.folder);
+    if is_symlink(&plugin_path) {
+        return unlink_plugin(&state_path, &plugin_path, &plugin.folder);
+    }
+
+    if is_dry_run() {
+        let keeps_data = !purge && plugin_path.join(PLUGIN_DATA_FOLDER).is_dir();
+        let mut heading = format!("Would remove {}", plugin_path.to_string_lossy());
+        if keeps_data {
+            heading.push_str(&format!(
+                "\nWould keep the data of {plugin_name} for a later reinstall"
+            ));
+        }
+        println!(
+            "{}",
+            format_plan(&heading, &plan_remove(&plugin_path, keeps_data))
+        );
+
+        if purge {
+            if let Some(data) = list_stashed_data(&state_path)?
+                .iter()
+                .find(|d| d.folder == plugin.folder)
+            {
+                println!(
+                    "Would delete data kept from a previous removal ({})",
+                    format_size(data.size)
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let kept_data = !purge && stash_plugin_data(&state_path, &plugin_path, &plugin.folder)?;
+    if purge
+        && list_stashed_data(&state_path)?
+            .iter()
+            .any(|d| d.folder == plugin.folder)
+    {
+        remove_stashed_data(&state_path, &plugin.folder)?;
+    }
+
+    fs::remove_dir_all(plugin_path).or(Err("There was an error removing the plugin"))?;
+    remove_provenance(&state_path, &plugin.folder)?;
+    remove_link(&state_path, &plugin.folder)?;
 
     println!("Removed {plugin_name}");
+    if kept_data {
+        println!(
+            "Kept the data of {plugin_name} for a later reinstall, use `cpm remove --purge` to delete it"
+        );
+    }
+
+    Ok(())
+}
+
+pub fn verify_plugins(chatterino_path: &Path, plugin_name: Option<&String>) -> Result<(), String> {
+    let chatterino_plugins_path = chatterino_path.join("Plugins");
+    let state_path = get_cpm_state_path(chatterino_path);
+
+    let folders = match plugin_name {
+        Some(plugin_name) => vec![plugin_name.to_string()],
+        None => list_provenance(&state_path),
+    };
+    if folders.is_empty() {
+        println!("No plugins installed by cpm");
+        return Ok(());
+    }
+
+    let mut changed = 0;
+    for folder in folders {
+        let Some(provenance) = read_provenance(&state_path, &folder)? else {
+            return Err(format!(
+                "Plugin '{folder}' was not installed by cpm and cannot be verified."
+            ));
+        };
+
+        let plugin_path = chatterino_plugins_path.join(&folder);
+        if !plugin_path.is_dir() {
+            return Err(format!("Plugin '{folder}' not found."));
+        }
+        if provenance.files.is_empty() {
+            println!("Skipped {folder}, no file hashes were recorded when it was installed");
+            continue;
+        }
+
+        let changes = verify_plugin_files(&plugin_path, &provenance.files)?;
+        if changes.is_empty() {
+            println!("{folder}: all {} files match", provenance.files.len());
+            continue;
+        }
+
+        changed += 1;
+        println!("{folder}: {} file(s) changed since install", changes.len());
+        print_file_changes(&changes);
+    }
+
+    if changed > 0 {
+        return Err(format!(
+            "{changed} plugin(s) do not match what was installed"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Get the release archive a plugin was installed from, using the download cache
+///
+/// ### Arguments
+/// * `provenance` where the plugin was installed from
+/// * `asset` name of the release archive
+/// * `progress` progress to report downloaded bytes to
+fn get_installed_release_archive(
+    provenance: &Provenance,
+    asset: &str,
+    progress: &Progress,
+) -> Result<PathBuf, String> {
+    // plugins installed from a release have its tag as their ref
+    let (owner, repo, tag) = (&provenance.owner, &provenance.repo, &provenance.ref_);
+    let cache_path = get_release_cache_file(owner, repo, tag, asset)?;
+    if cache_path.is_file() {
+        return Ok(cache_path);
+    }
+
+    let release = get_release(owner, repo, Some(tag))?;
+    let archive = release
+        .find_asset(asset)
+        .ok_or(format!("Release {tag} no longer has {asset}"))?;
+    download_release_archive(owner, repo, &release, archive, progress)
+}
+
+pub fn diff_plugin(chatterino_path: &Path, plugin_name: &str) -> Result<(), String> {
+    let plugin_path = chatterino_path.join("Plugins").join(plugin_name);
+    let state_path = get_cpm_state_path(chatterino_path);
+
+    let provenance = read_provenance(&state_path, plugin_name)?.ok_or(format!(
+        "Plugin '{plugin_name}' was not installed by cpm and has nothing to compare to."
+    ))?;
+    if !plugin_path.is_dir() {
+        return Err(format!("Plugin '{plugin_name}' not found."));
+    }
+
+    // the original archive is usually still in the download cache
+    let progress = Progress::new(plugin_name);
+    // compare against the files installing it would have extracted
+    let original = match &provenance.asset {
+        Some(asset) => get_installed_release_archive(&provenance, asset, &progress),
+        None => download_tarball(
+            &provenance.owner,
+            &provenance.repo,
+            &provenance.commit,
+            &progress,
+        ),
+    }
+    .and_then(|path| read_plugin_archive(&path, provenance.asset.is_some(), &provenance.exclude));
+    progress.clear();
+    let original = original?;
+
+    let mut paths: Vec<String> = original
+        .keys()
+        .cloned()
+        .chain(list_files(&plugin_path).into_iter().map(|(path, _)| path))
+        .filter(|path| !is_plugin_data_path(path))
+        .collect();
+    paths.sort();
+    paths.dedup();
+
+    let mut differs = false;
+    for path in paths {
+        let installed = fs::read(plugin_path.join(&path)).ok();
+        let diff = format_file_diff(
+            &path,
+            original.get(&path).map(|f| f.as_slice()),
+            installed.as_deref(),
+        );
+
+        if let Some(diff) = diff {
+            differs = true;
+            print!("{diff}");
+        }
+    }
+
+    if !differs {
+        println!(
+            "{plugin_name} matches {}/{} at {}",
+            provenance.owner,
+            provenance.repo,
+            short_commit(&provenance.commit)
+        );
+    }
 
     Ok(())
 }
 
-pub fn plugin_info(chatterino_path: Option<&String>, plugin_name: String) -> Result<(), String> {
+pub fn plugin_info(
+    chatterino_path: &Path,
+    plugin_name: String,
+    is_remote: bool,
+) -> Result<(), String> {
     // get chatterino plugins folder path
-    let chatterino_plugins_path = if let Some(chatterino_path) = chatterino_path {
-        Path::new(chatterino_path).to_owned().join("Plugins")
+    let chatterino_plugins_path = chatterino_path.join("Plugins");
+
+    // anything that isn't an installed plugin is looked up on GitHub
+    let is_url = Url::parse(&plugin_name).is_ok();
+    let installed = if is_remote || is_url {
+        None
     } else {
-        get_default_chatterino_path()?.join("Plugins")
+        parse_plugins(&chatterino_plugins_path)?
+            .into_iter()
+            .find(|p| p.folder == plugin_name)
     };
 
-    let plugins = parse_plugins(&chatterino_plugins_path)?;
-    let plugin = plugins
-        .iter()
-        .find(|p| p.folder == plugin_name)
-        .ok_or(format!("Plugin '{plugin_name}' not found."))?;
+    let Some(plugin) = installed else {
+        if is_remote || is_url {
+            return remote_plugin_info(&plugin_name, is_url);
+        }
+        return remote_plugin_info(&plugin_name, false)
+            .map_err(|e| format!("Plugin '{plugin_name}' is not installed. {e}"));
+    };
+
+    let mut extra_rows = Vec::new();
+    let state_path = get_cpm_state_path(chatterino_path);
+    if let Some(provenance) = read_provenance(&state_path, &plugin.folder)? {
+        extra_rows.push(("Source".to_string(), provenance.source));
+        extra_rows.push((
+            "Installed Commit".to_string(),
+            format!("{} ({})", provenance.commit, provenance.ref_),
+        ));
+    }
+
+    if let Some(link) = read_link(&state_path, &plugin.folder)? {
+        let kind = if link.mirror {
+            "Mirror Of"
+        } else {
+            "Linked To"
+        };
+        extra_rows.push((kind.to_string(), link.source.to_string_lossy().to_string()));
+    }
+
+    let installed = installed_registrations(chatterino_path)?;
+    let registrations = scan_registrations(&chatterino_plugins_path.join(&plugin.folder));
+    let conflicts = find_plugin_conflicts(&plugin.folder, &registrations, &installed);
+    extra_rows.push(("Commands".to_string(), registrations.commands.join(", ")));
+    extra_rows.push(("Callbacks".to_string(), registrations.callbacks.join(", ")));
+    if !conflicts.is_empty() {
+        let conflicts: Vec<String> = conflicts
+            .into_iter()
+            .map(|(command, owners)| format!("{command} (also {})", owners.join(", ")))
+            .collect();
+        extra_rows.push(("Command Conflicts".to_string(), conflicts.join(", ")));
+    }
+
+    print_plugin_info(plugin, extra_rows);
 
-    print_plugin_info(plugin.clone());
+    Ok(())
+}
+
+fn remote_plugin_info(plugin: &str, is_repo: bool) -> Result<(), String> {
+    let indexes = if is_repo_source(plugin, is_repo) {
+        Vec::new()
+    } else {
+        get_indexes()?
+    };
+    let source = resolve_plugin_source(plugin, is_repo, &indexes)?;
+    let repo_info = get_repo_info(&source.owner, &source.repo)?;
+    let git_ref = source.git_ref.unwrap_or(repo_info.default_branch.clone());
+
+    let info_json =
+        get_file_contents(&source.owner, &source.repo, &git_ref, "info.json")?.ok_or(format!(
+            "info.json not found in {}/{} at {git_ref}",
+            source.owner, source.repo
+        ))?;
+    let json: serde_json::Value = serde_json::from_str(&info_json)
+        .or(Err("There was an error parsing the info.json plugin file"))?;
+
+    let problems = validate_plugin_info(&json);
+    if !problems.is_empty() {
+        return Err(format!("Invalid info.json: {}", problems.join(", ")));
+    }
+
+    let extra_rows = vec![
+        (
+            "Source".to_string(),
+            format!("https://github.com/{}/{}", source.owner, source.repo),
+        ),
+        ("Ref".to_string(), git_ref),
+        ("Stars".to_string(), repo_info.stars.to_string()),
+        (
+            "Last Push".to_string(),
+            repo_info.pushed_at.unwrap_or("Unknown".to_string()),
+        ),
+        (
+            "Archived".to_string(),
+            if repo_info.archived { "Yes" } else { "No" }.to_string(),
+        ),
+        (
+            "Repository Licence".to_string(),
+            repo_info.licence.unwrap_or("None".to_string()),
+        ),
+    ];
+
+    print_plugin_info(parse_plugin_info(source.folder, &json)?, extra_rows);
+
+    Ok(())
+}
+
+pub fn list_data(chatterino_path: &Path) -> Result<(), String> {
+    let state_path = get_cpm_state_path(chatterino_path);
+
+    let stashed = list_stashed_data(&state_path)?;
+    if stashed.is_empty() {
+        println!("No data kept from removed plugins");
+        return Ok(());
+    }
+    print_stashed_data(stashed);
+
+    Ok(())
+}
+
+pub fn prune_data(chatterino_path: &Path, plugin_name: Option<&String>) -> Result<(), String> {
+    let state_path = get_cpm_state_path(chatterino_path);
+
+    let stashed = list_stashed_data(&state_path)?;
+    let folders: Vec<String> = match plugin_name {
+        Some(plugin_name) => {
+            let data = stashed
+                .iter()
+                .find(|d| &d.folder == plugin_name)
+                .ok_or(format!("No data kept for '{plugin_name}'."))?;
+            vec![data.folder.clone()]
+        }
+        None => stashed.iter().map(|d| d.folder.clone()).collect(),
+    };
+
+    for folder in folders {
+        if is_dry_run() {
+            let size = stashed
+                .iter()
+                .find(|d| d.folder == folder)
+                .map(|d| d.size)
+                .unwrap_or(0);
+            println!("Would delete data of {folder} ({})", format_size(size));
+            continue;
+        }
+
+        remove_stashed_data(&state_path, &folder)?;
+        println!("Deleted data of {folder}");
+    }
+
+    Ok(())
+}
+
+pub fn get_config(key: &str) -> Result<(), String> {
+    let config = read_config()?;
+    match get_config_value(&config, key) {
+        Some(Value::String(value)) => println!("{value}"),
+        Some(Value::Table(table)) => {
+            for (key, value) in flatten_config(table) {
+                println!("{key} = {value}");
+            }
+        }
+        Some(value) => println!("{value}"),
+        None => return Err(format!("Config key '{key}' is not set")),
+    }
+
+    Ok(())
+}
+
+pub fn set_config(key: &str, value: &str) -> Result<(), String> {
+    validate_config_key(key)?;
+
+    let mut config = read_config()?;
+    if key == "default_profile" && get_config_value(&config, &format!("profiles.{value}")).is_none()
+    {
+        return Err(format!("Profile '{value}' not found."));
+    }
+    if key == SIGNATURE_POLICY_KEY {
+        parse_signature_policy(value)?;
+    }
+
+    set_config_value(&mut config, key, parse_config_value(key, value))?;
+    if is_dry_run() {
+        println!("Would set {key} to {value}");
+        return Ok(());
+    }
+    write_config(&config)?;
+
+    println!("Set {key} to {value}");
+
+    Ok(())
+}
+
+pub fn unset_config(key: &str) -> Result<(), String> {
+    validate_config_key(key)?;
+
+    let mut config = read_config()?;
+    if !unset_config_value(&mut config, key) {
+        return Err(format!("Config key '{key}' is not set"));
+    }
+    if is_dry_run() {
+        println!("Would unset {key}");
+        return Ok(());
+    }
+    write_config(&config)?;
+
+    println!("Unset {key}");
+
+    Ok(())
+}
+
+pub fn list_config() -> Result<(), String> {
+    let config: Table = read_config()?;
+
+    let entries = flatten_config(&config);
+    if entries.is_empty() {
+        println!(
+            "No config set, the config file is {}",
+            get_config_path()?.to_string_lossy()
+        );
+        return Ok(());
+    }
+    print_config(entries);
+
+    Ok(())
+}
+
+pub fn doctor(chatterino_path: &Path, fix: bool) -> Result<(), String> {
+    let checks = run_checks(chatterino_path, fix && !is_dry_run());
+    print_checks(&checks);
+
+    if fix && is_dry_run() {
+        println!("Nothing was repaired because of --dry-run");
+    }
+
+    Ok(())
+}
+
+pub fn search_plugins(query: Option<&String>, tags: Vec<String>) -> Result<(), String> {
+    let indexes = get_indexes()?;
+
+    let plugins = search_indexes(&indexes, query.map(|q| q.as_str()), &tags);
+    if plugins.is_empty() {
+        println!("No plugins found");
+        return Ok(());
+    }
+    print_index_plugins(plugins);
+
+    Ok(())
+}
+
+pub fn add_index(name: &str, location: &str, priority: i64) -> Result<(), String> {
+    validate_index_name(name)?;
+
+    let mut config = read_config()?;
+    if get_index_sources(&config).iter().any(|s| s.name == name) {
+        return Err(format!("Plugin index '{name}' already exists."));
+    }
+
+    // local indexes are stored absolute so they work from any folder
+    let location = match Url::parse(location) {
+        Ok(_) => location.to_string(),
+        Err(_) => fs::canonicalize(location)
+            .or(Err(format!("Plugin index {location} not found.")))?
+            .to_string_lossy()
+            .to_string(),
+    };
+
+    let source = IndexSource {
+        name: name.to_string(),
+        location: location.clone(),
+        priority,
+    };
+    let index = get_index(&source, true)?;
+
+    set_config_value(
+        &mut config,
+        &format!("indexes.{name}.url"),
+        Value::String(location),
+    )?;
+    set_config_value(
+        &mut config,
+        &format!("indexes.{name}.priority"),
+        Value::Integer(priority),
+    )?;
+    if is_dry_run() {
+        println!(
+            "Would add index {name} with {} plugins",
+            index.plugins.len()
+        );
+        return Ok(());
+    }
+    write_config(&config)?;
+
+    println!("Added index {name} with {} plugins", index.plugins.len());
+
+    Ok(())
+}
+
+pub fn list_indexes() -> Result<(), String> {
+    let sources = get_index_sources(&read_config()?);
+    if sources.is_empty() {
+        println!("No plugin indexes configured");
+        return Ok(());
+    }
+    print_index_sources(sources);
+
+    Ok(())
+}
+
+pub fn remove_index(name: &str) -> Result<(), String> {
+    let mut config = read_config()?;
+    if !get_index_sources(&config).iter().any(|s| s.name == name) {
+        return Err(format!("Plugin index '{name}' not found."));
+    }
+
+    // the default index may still be configured the old way
+    let removed = unset_config_value(&mut config, &format!("indexes.{name}"));
+    if !removed && name == DEFAULT_INDEX_NAME {
+        unset_config_value(&mut config, "index");
+    }
+    if is_dry_run() {
+        println!("Would remove index {name}");
+        return Ok(());
+    }
+    write_config(&config)?;
+    remove_cached_index(name)?;
+
+    println!("Removed index {name}");
+
+    Ok(())
+}
+
+pub fn add_trusted_key(key: &str, repos: &[String]) -> Result<(), String> {
+    let mut trusted_key = TrustedKey::parse(key)?;
+    for repo in repos {
+        if !is_valid_repo(repo) {
+            return Err(format!("Invalid repository '{repo}', use owner/repo"));
+        }
+        trusted_key.repos.push(repo.clone());
+    }
+
+    // adding a key again adds to the repositories it is trusted for, trusted for all stays all
+    let mut config = read_config()?;
+    if let Some(existing) = get_trusted_keys(&config)
+        .into_iter()
+        .find(|k| k.id == trusted_key.id)
+    {
+        trusted_key.repos = if existing.repos.is_empty() || trusted_key.repos.is_empty() {
+            Vec::new()
+        } else {
+            let mut repos = existing.repos;
+            for repo in trusted_key.repos {
+                if !repos.iter().any(|r| r.eq_ignore_ascii_case(&repo)) {
+                    repos.push(repo);
+                }
+            }
+            repos
+        };
+    }
+    set_trusted_key(&mut config, &trusted_key)?;
+
+    let scope = if trusted_key.repos.is_empty() {
+        "all repositories".to_string()
+    } else {
+        trusted_key.repos.join(", ")
+    };
+    if is_dry_run() {
+        println!("Would trust key {} for {scope}", trusted_key.id);
+        return Ok(());
+    }
+    write_config(&config)?;
+
+    println!("Trusted key {} for {scope}", trusted_key.id);
+
+    Ok(())
+}
+
+pub fn list_trusted_keys() -> Result<(), String> {
+    let keys = get_trusted_keys(&read_config()?);
+    if keys.is_empty() {
+        println!("No trusted keys, add one with `cpm trust add <key>`");
+        return Ok(());
+    }
+
+    print_trusted_keys(&keys);
+
+    Ok(())
+}
+
+pub fn remove_trusted_key(id: &str) -> Result<(), String> {
+    let mut config = read_config()?;
+    // look at the raw entries, so a key skipped for a broken scope can still be removed
+    let id = get_config_value(&config, "trust")
+        .and_then(|v| v.as_table())
+        .and_then(|keys| keys.keys().find(|k| k.eq_ignore_ascii_case(id)).cloned())
+        .ok_or(format!("Key '{id}' is not trusted."))?;
+
+    unset_config_value(&mut config, &format!("trust.{id}"));
+    if is_dry_run() {
+        println!("Would remove key {id}");
+        return Ok(());
+    }
+    write_config(&config)?;
+
+    println!("Removed key {id}");
+
+    Ok(())
+}
+
+pub fn refresh_indexes(name: Option<&String>) -> Result<(), String> {
+    let sources = get_index_sources(&read_config()?);
+    if let Some(name) = name {
+        if !sources.iter().any(|s| &s.name == name) {
+            return Err(format!("Plugin index '{name}' not found."));
+        }
+    }
+
+    for source in sources {
+        if name.is_some_and(|n| n != &source.name) {
+            continue;
+        }
+
+        match get_index(&source, true) {
+            Ok(index) => println!(
+                "Refreshed index {} ({} plugins)",
+                source.name,
+                index.plugins.len()
+            ),
+            Err(message) => println!("Error: {message}"),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn list_cache() -> Result<(), String> {
+    let archives = list_cached_archives()?;
+    let (responses, responses_size) = get_cached_responses_size()?;
+
+    if archives.is_empty() && responses == 0 {
+        println!("The download cache is empty");
+        return Ok(());
+    }
+
+    if !archives.is_empty() {
+        print_cached_archives(archives);
+    }
+    println!(
+        "{responses} GitHub API response(s) cached ({})",
+        format_size(responses_size)
+    );
+    println!("Cache folder is {}", get_cache_path()?.to_string_lossy());
+
+    Ok(())
+}
+
+pub fn clean_download_cache() -> Result<(), String> {
+    if is_dry_run() {
+        let archives_size: u64 = list_cached_archives()?.iter().map(|a| a.size).sum();
+        let (_, responses_size) = get_cached_responses_size()?;
+        println!(
+            "Would delete the download cache ({})",
+            format_size(archives_size + responses_size)
+        );
+        return Ok(());
+    }
+
+    clean_cache()?;
+    println!("Deleted the download cache");
+
+    Ok(())
+}
+
+pub fn prune_download_cache(older_than: &str) -> Result<(), String> {
+    let older_than = parse_duration(older_than)?;
+
+    let (removed, freed) = prune_cache(older_than, is_dry_run())?;
+    let verb = if is_dry_run() {
+        "Would delete"
+    } else {
+        "Deleted"
+    };
+    println!(
+        "{verb} {removed} cached download(s), freeing {}",
+        format_size(freed)
+    );
 
     Ok(())
 }
@@ -1,4 +1,14 @@
-use clap::{arg, command, Arg, ArgAction, Command};
+use clap::{arg, command, Arg, ArgAction, ArgMatches, Command};
+
+use commands::InstallOptions;
+use utils::{
+    common::{Plugin, PluginPermission},
+    config::get_chatterino_path,
+    github::{set_offline, set_wait_for_rate_limit},
+    plan::set_dry_run,
+    plugin::PLUGIN_PERMISSION_TYPES,
+    progress::set_quiet,
+};
 
 mod commands;
 mod utils;
@@ -12,11 +22,89 @@ fn main() {
                 .alias("install")
                 .alias("i")
                 .about("Install plugin")
-                .arg(Arg::new("plugin").required(true))
+                .arg(
+                    Arg::new("plugin")
+                        .required(true)
+                        .num_args(1..)
+                        .help("GitHub repository URL or [index/]name[@version]"),
+                )
                 .arg(
                     Arg::new("repo")
                         .short('r')
                         .long("repo")
+                        .help("Treat the plugins as GitHub repository URLs")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("release")
+                        .long("release")
+                        .help("Install the archive attached to a release, made with `cpm pack`")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(jobs_arg())
+                .arg(fail_fast_arg())
+                .arg(skip_lua_check_arg())
+                .arg(exclude_arg()),
+        )
+        .subcommand(
+            Command::new("update")
+                .alias("up")
+                .about("Update installed plugins")
+                .arg(
+                    Arg::new("plugin")
+                        .required_unless_present("all")
+                        .num_args(1..),
+                )
+                .arg(
+                    Arg::new("all")
+                        .short('a')
+                        .long("all")
+                        .help("Update all plugins installed by cpm")
+                        .conflicts_with("plugin")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(jobs_arg())
+                .arg(fail_fast_arg())
+                .arg(skip_lua_check_arg())
+                .arg(exclude_arg()),
+        )
+        .subcommand(
+            Command::new("reinstall")
+                .about("Reinstall a plugin at the commit it was installed at")
+                .arg(Arg::new("plugin").required(true))
+                .arg(skip_lua_check_arg())
+                .arg(exclude_arg()),
+        )
+        .subcommand(
+            Command::new("adopt")
+                .about("Let cpm manage a plugin that was installed by hand")
+                .arg(Arg::new("plugin").required(true))
+                .arg(
+                    Arg::new("source")
+                        .short('s')
+                        .long("source")
+                        .help("GitHub repository the plugin came from"),
+                ),
+        )
+        .subcommand(
+            Command::new("link")
+                .about("Link a plugin's working copy into the Plugins folder")
+                .arg(
+                    Arg::new("source")
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Path to the plugin folder"),
+                )
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .help("Folder name in Plugins, defaults to the folder's name"),
+                )
+                .arg(
+                    Arg::new("watch")
+                        .short('w')
+                        .long("watch")
+                        .help("Keep a copy in sync instead of creating a symlink")
                         .action(ArgAction::SetTrue),
                 ),
         )
@@ -25,45 +113,587 @@ fn main() {
                 .alias("uninstall")
                 .alias("rm")
                 .about("Uninstall plugin")
-                .arg(Arg::new("plugin").required(true)),
+                .arg(Arg::new("plugin").required(true))
+                .arg(
+                    Arg::new("purge")
+                        .long("purge")
+                        .help("Also delete the plugin's data")
+                        .action(ArgAction::SetTrue),
+                ),
         )
         .subcommand(
             Command::new("info")
                 .about("Get plugin info")
-                .arg(Arg::new("plugin").required(true)),
+                .arg(
+                    Arg::new("plugin")
+                        .required(true)
+                        .help("Installed plugin, GitHub repository URL or [index/]name[@version]"),
+                )
+                .arg(
+                    Arg::new("remote")
+                        .short('r')
+                        .long("remote")
+                        .help("Look the plugin up remotely even if it is installed")
+                        .action(ArgAction::SetTrue),
+                ),
         )
         .subcommand(
             Command::new("list")
                 .alias("ls")
-                .about("List installed plugins"),
+                .about("List installed plugins")
+                .arg(
+                    Arg::new("conflicts")
+                        .long("conflicts")
+                        .help("List commands registered by more than one plugin")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Check a plugin folder for problems")
+                .arg(
+                    Arg::new("plugin")
+                        .help("Path to the plugin folder or name of an installed plugin")
+                        .default_value("."),
+                ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .about("Check what a plugin's code does against its permissions")
+                .arg(
+                    Arg::new("plugin")
+                        .help("Path to the plugin folder or name of an installed plugin")
+                        .default_value("."),
+                ),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Load a plugin with a mock of Chatterino's API")
+                .arg(
+                    Arg::new("plugin")
+                        .help("Path to the plugin folder or name of an installed plugin")
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::new("run")
+                        .short('r')
+                        .long("run")
+                        .help("Run a command the plugin registered, e.g. \"/tr en hello\"")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("channel")
+                        .long("channel")
+                        .help("Channel commands are run in")
+                        .default_value("test"),
+                ),
+        )
+        .subcommand(
+            Command::new("new")
+                .about("Create a new plugin from a template")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Folder name of the plugin"),
+                )
+                .arg(
+                    Arg::new("in-plugins")
+                        .long("in-plugins")
+                        .help("Create it in Chatterino's Plugins folder")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .help("Don't ask for anything not given as an option")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(Arg::new("description").long("description"))
+                .arg(Arg::new("author").long("author").action(ArgAction::Append))
+                .arg(Arg::new("licence").long("licence"))
+                .arg(Arg::new("tag").long("tag").action(ArgAction::Append))
+                .arg(
+                    Arg::new("permission")
+                        .long("permission")
+                        .value_parser(PLUGIN_PERMISSION_TYPES)
+                        .action(ArgAction::Append),
+                ),
+        )
+        .subcommand(
+            Command::new("pack")
+                .about("Pack a plugin into an archive for a release")
+                .arg(
+                    Arg::new("plugin")
+                        .help("Path to the plugin folder or name of an installed plugin")
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Folder to write the archive to, defaults to the current folder"),
+                )
+                .arg(
+                    Arg::new("zip")
+                        .long("zip")
+                        .help("Also write a .zip")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check installed plugins for files changed since install")
+                .arg(Arg::new("plugin").help("Only verify this plugin")),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Show changes to a plugin since install")
+                .arg(Arg::new("plugin").required(true)),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Search plugin indexes")
+                .arg(Arg::new("query").required_unless_present("tag"))
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .help("Only show plugins with this tag")
+                        .action(ArgAction::Append),
+                ),
+        )
+        .subcommand(
+            Command::new("index")
+                .about("Manage plugin indexes")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a plugin index")
+                        .arg(Arg::new("name").required(true))
+                        .arg(
+                            Arg::new("location")
+                                .required(true)
+                                .help("URL or path of the index file"),
+                        )
+                        .arg(
+                            Arg::new("priority")
+                                .long("priority")
+                                .help("Indexes with a higher priority win when names collide")
+                                .value_parser(clap::value_parser!(i64))
+                                .default_value("0"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .alias("ls")
+                        .about("List plugin indexes"),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .alias("rm")
+                        .about("Remove a plugin index")
+                        .arg(Arg::new("name").required(true)),
+                )
+                .subcommand(
+                    Command::new("refresh")
+                        .about("Fetch plugin indexes again")
+                        .arg(Arg::new("name").help("Only refresh this index")),
+                ),
+        )
+        .subcommand(
+            Command::new("trust")
+                .about("Manage keys trusted to sign plugin archives")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Trust a minisign public key")
+                        .arg(
+                            Arg::new("key")
+                                .required(true)
+                                .help("The public key, or the path to a minisign.pub"),
+                        )
+                        .arg(
+                            Arg::new("for")
+                                .long("for")
+                                .value_name("OWNER/REPO")
+                                .help("Only trust the key for this repository, can be repeated")
+                                .action(ArgAction::Append),
+                        ),
+                )
+                .subcommand(Command::new("list").alias("ls").about("List trusted keys"))
+                .subcommand(
+                    Command::new("remove")
+                        .alias("rm")
+                        .about("Stop trusting a key")
+                        .arg(Arg::new("id").required(true).help("ID of the key")),
+                ),
+        )
+        .subcommand(
+            Command::new("cache")
+                .about("Manage the download cache")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .alias("ls")
+                        .about("List cached downloads"),
+                )
+                .subcommand(Command::new("clean").about("Delete the whole download cache"))
+                .subcommand(
+                    Command::new("prune")
+                        .about("Delete old cached downloads")
+                        .arg(
+                            Arg::new("older-than")
+                                .long("older-than")
+                                .help("Age of downloads to delete, e.g. 30d, 12h")
+                                .default_value("30d"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("data")
+                .about("Manage data kept from removed plugins")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .alias("ls")
+                        .about("List data kept from removed plugins"),
+                )
+                .subcommand(
+                    Command::new("prune")
+                        .about("Delete data kept from removed plugins")
+                        .arg(Arg::new("plugin").help("Only delete the data of this plugin")),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the Chatterino folder for problems")
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .help("Repair problems that are safe to repair")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Manage cpm config")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("get")
+                        .about("Get a config value")
+                        .arg(Arg::new("key").required(true)),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Set a config value, e.g. `profiles.nightly <path>`")
+                        .arg(Arg::new("key").required(true))
+                        .arg(Arg::new("value").required(true)),
+                )
+                .subcommand(
+                    Command::new("unset")
+                        .about("Remove a config value")
+                        .arg(Arg::new("key").required(true)),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .alias("ls")
+                        .about("List all config values"),
+                ),
+        )
+        .arg(arg!(-p --path <path> "Path to Chatterino folder").global(true))
+        .arg(arg!(--offline "Only use the download cache, never the network").global(true))
+        .arg(
+            arg!(--"wait-for-rate-limit" "Wait for GitHub rate limits to reset instead of failing")
+                .global(true),
+        )
+        .arg(arg!(-n --"dry-run" "Show what would change without changing anything").global(true))
+        .arg(arg!(-q --quiet "Don't show progress or every file written").global(true))
+        .arg(
+            arg!(-P --profile <name> "Chatterino profile from the cpm config to use")
+                .global(true)
+                .conflicts_with("path"),
         )
-        .arg(arg!(-p --path <path> "Path to Chatterino folder"))
         .version(VERSION_STR)
         .arg_required_else_help(true)
         .get_matches();
 
-    if let Some((name, submatches)) = matches.subcommand() {
-        let chatterino_path = matches.get_one::<String>("path");
+    if let Err(message) = run(&matches) {
+        eprintln!("Error: {message}");
+        std::process::exit(1);
+    }
+}
+
+fn jobs_arg() -> Arg {
+    Arg::new("jobs")
+        .short('j')
+        .long("jobs")
+        .help("How many plugins to download at the same time")
+        .value_parser(clap::value_parser!(usize))
+        .default_value("4")
+}
 
-        if let Err(message) = match name {
-            "get" => {
-                let plugin = submatches.get_one::<String>("plugin").unwrap();
-                let is_repo = submatches.get_flag("repo");
+fn skip_lua_check_arg() -> Arg {
+    Arg::new("skip-lua-check")
+        .long("skip-lua-check")
+        .help("Install even if the plugin's Lua code has syntax errors")
+        .action(ArgAction::SetTrue)
+}
+
+fn exclude_arg() -> Arg {
+    Arg::new("exclude")
+        .long("exclude")
+        .value_name("PATTERN")
+        .help("Leave out files matching a pattern in .gitignore syntax, can be repeated")
+        .action(ArgAction::Append)
+}
 
-                commands::get_plugin(plugin, is_repo, chatterino_path)
+fn install_options(matches: &ArgMatches) -> InstallOptions {
+    InstallOptions {
+        skip_lua_check: matches.get_flag("skip-lua-check"),
+        // only `get` chooses, updates follow how a plugin was installed
+        release: matches
+            .try_get_one::<bool>("release")
+            .ok()
+            .flatten()
+            .is_some_and(|r| *r),
+        exclude: matches
+            .get_many::<String>("exclude")
+            .map(|e| e.cloned().collect())
+            .unwrap_or_default(),
+    }
+}
+
+fn fail_fast_arg() -> Arg {
+    Arg::new("fail-fast")
+        .long("fail-fast")
+        .help("Stop at the first plugin that fails")
+        .action(ArgAction::SetTrue)
+}
+
+fn run(matches: &ArgMatches) -> Result<(), String> {
+    let Some((name, submatches)) = matches.subcommand() else {
+        return Ok(());
+    };
+
+    set_offline(matches.get_flag("offline"));
+    set_quiet(matches.get_flag("quiet"));
+    set_dry_run(matches.get_flag("dry-run"));
+    set_wait_for_rate_limit(matches.get_flag("wait-for-rate-limit"));
+
+    let chatterino_path = || {
+        get_chatterino_path(
+            matches.get_one::<String>("path"),
+            matches.get_one::<String>("profile"),
+        )
+    };
+
+    match name {
+        "get" => {
+            let plugins = submatches
+                .get_many::<String>("plugin")
+                .unwrap()
+                .cloned()
+                .collect();
+            let is_repo = submatches.get_flag("repo");
+            let jobs = *submatches.get_one::<usize>("jobs").unwrap();
+            let fail_fast = submatches.get_flag("fail-fast");
+
+            commands::get_plugins(
+                plugins,
+                is_repo,
+                &chatterino_path()?,
+                jobs,
+                fail_fast,
+                &install_options(submatches),
+            )
+        }
+        "update" => {
+            let plugins = submatches
+                .get_many::<String>("plugin")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            let jobs = *submatches.get_one::<usize>("jobs").unwrap();
+            let fail_fast = submatches.get_flag("fail-fast");
+
+            commands::update_plugins(
+                &chatterino_path()?,
+                plugins,
+                jobs,
+                fail_fast,
+                &install_options(submatches),
+            )
+        }
+        "reinstall" => {
+            let plugin = submatches.get_one::<String>("plugin").unwrap();
+            commands::reinstall_plugin(&chatterino_path()?, plugin, &install_options(submatches))
+        }
+        "adopt" => {
+            let plugin = submatches.get_one::<String>("plugin").unwrap();
+            let source = submatches.get_one::<String>("source");
+            commands::adopt_plugin(&chatterino_path()?, plugin, source)
+        }
+        "list" => {
+            let conflicts = submatches.get_flag("conflicts");
+            commands::list_plugins(&chatterino_path()?, conflicts)
+        }
+        "validate" => {
+            let plugin = submatches.get_one::<String>("plugin").unwrap();
+            commands::validate_plugin(plugin, chatterino_path)
+        }
+        "audit" => {
+            let plugin = submatches.get_one::<String>("plugin").unwrap();
+            commands::audit_plugin(plugin, chatterino_path)
+        }
+        "test" => {
+            let plugin = submatches.get_one::<String>("plugin").unwrap();
+            let commands: Vec<String> = submatches
+                .get_many::<String>("run")
+                .map(|c| c.cloned().collect())
+                .unwrap_or_default();
+            let channel = submatches.get_one::<String>("channel").unwrap();
+            commands::test_plugin(plugin, chatterino_path, &commands, channel)
+        }
+        "new" => {
+            let name = submatches.get_one::<String>("name").unwrap();
+            let strings = |id: &str| -> Vec<String> {
+                submatches
+                    .get_many::<String>(id)
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect()
+            };
+
+            let mut plugin = Plugin::new();
+            plugin.description = submatches.get_one::<String>("description").cloned();
+            plugin.authors = strings("author");
+            plugin.licence = submatches.get_one::<String>("licence").cloned();
+            plugin.tags = strings("tag");
+            plugin.permissions = strings("permission")
+                .into_iter()
+                .map(|type_| PluginPermission { type_ })
+                .collect();
+
+            let chatterino_path = if submatches.get_flag("in-plugins") {
+                Some(chatterino_path()?)
+            } else {
+                None
+            };
+            let ask = !submatches.get_flag("yes");
+            commands::new_plugin(name, chatterino_path.as_deref(), plugin, ask)
+        }
+        "pack" => {
+            let plugin = submatches.get_one::<String>("plugin").unwrap();
+            let output = submatches.get_one::<String>("output");
+            let zip = submatches.get_flag("zip");
+            commands::pack_plugin(plugin, chatterino_path, output, zip)
+        }
+        "verify" => {
+            let plugin = submatches.get_one::<String>("plugin");
+            commands::verify_plugins(&chatterino_path()?, plugin)
+        }
+        "diff" => {
+            let plugin = submatches.get_one::<String>("plugin").unwrap();
+            commands::diff_plugin(&chatterino_path()?, plugin)
+        }
+        "link" => {
+            let source = submatches.get_one::<String>("source").unwrap();
+            let name = submatches.get_one::<String>("name");
+            let watch = submatches.get_flag("watch");
+            commands::link_plugin(&chatterino_path()?, source, name, watch)
+        }
+        "remove" => {
+            let plugin = submatches.get_one::<String>("plugin").unwrap();
+            let purge = submatches.get_flag("purge");
+            commands::remove_plugin(&chatterino_path()?, plugin.to_string(), purge)
+        }
+        "info" => {
+            let plugin = submatches.get_one::<String>("plugin").unwrap();
+            let is_remote = submatches.get_flag("remote");
+            commands::plugin_info(&chatterino_path()?, plugin.to_string(), is_remote)
+        }
+        "search" => {
+            let query = submatches.get_one::<String>("query");
+            let tags = submatches
+                .get_many::<String>("tag")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            commands::search_plugins(query, tags)
+        }
+        "index" => match submatches.subcommand() {
+            Some(("add", index_matches)) => {
+                let name = index_matches.get_one::<String>("name").unwrap();
+                let location = index_matches.get_one::<String>("location").unwrap();
+                let priority = *index_matches.get_one::<i64>("priority").unwrap();
+                commands::add_index(name, location, priority)
+            }
+            Some(("list", _)) => commands::list_indexes(),
+            Some(("remove", index_matches)) => {
+                let name = index_matches.get_one::<String>("name").unwrap();
+                commands::remove_index(name)
+            }
+            Some(("refresh", index_matches)) => {
+                let name = index_matches.get_one::<String>("name");
+                commands::refresh_indexes(name)
             }
-            "list" => commands::list_plugins(chatterino_path),
-            "remove" => {
-                let plugin = submatches.get_one::<String>("plugin").unwrap();
-                commands::remove_plugin(chatterino_path, plugin.to_string())
+            _ => Err("Command not found!".to_string()),
+        },
+        "trust" => match submatches.subcommand() {
+            Some(("add", trust_matches)) => {
+                let key = trust_matches.get_one::<String>("key").unwrap();
+                let repos: Vec<String> = trust_matches
+                    .get_many::<String>("for")
+                    .map(|r| r.cloned().collect())
+                    .unwrap_or_default();
+                commands::add_trusted_key(key, &repos)
+            }
+            Some(("list", _)) => commands::list_trusted_keys(),
+            Some(("remove", trust_matches)) => {
+                let id = trust_matches.get_one::<String>("id").unwrap();
+                commands::remove_trusted_key(id)
             }
-            "info" => {
-                let plugin = submatches.get_one::<String>("plugin").unwrap();
-                commands::plugin_info(chatterino_path, plugin.to_string())
+            _ => Err("Command not found!".to_string()),
+        },
+        "cache" => match submatches.subcommand() {
+            Some(("list", _)) => commands::list_cache(),
+            Some(("clean", _)) => commands::clean_download_cache(),
+            Some(("prune", cache_matches)) => {
+                let older_than = cache_matches.get_one::<String>("older-than").unwrap();
+                commands::prune_download_cache(older_than)
+            }
+            _ => Err("Command not found!".to_string()),
+        },
+        "data" => match submatches.subcommand() {
+            Some(("list", _)) => commands::list_data(&chatterino_path()?),
+            Some(("prune", prune_matches)) => {
+                let plugin = prune_matches.get_one::<String>("plugin");
+                commands::prune_data(&chatterino_path()?, plugin)
             }
             _ => Err("Command not found!".to_string()),
-        } {
-            println!("Error: {message}");
+        },
+        "doctor" => {
+            let fix = submatches.get_flag("fix");
+            commands::doctor(&chatterino_path()?, fix)
         }
+        "config" => match submatches.subcommand() {
+            Some(("get", config_matches)) => {
+                let key = config_matches.get_one::<String>("key").unwrap();
+                commands::get_config(key)
+            }
+            Some(("set", config_matches)) => {
+                let key = config_matches.get_one::<String>("key").unwrap();
+                let value = config_matches.get_one::<String>("value").unwrap();
+                commands::set_config(key, value)
+            }
+            Some(("unset", config_matches)) => {
+                let key = config_matches.get_one::<String>("key").unwrap();
+                commands::unset_config(key)
+            }
+            Some(("list", _)) => commands::list_config(),
+            _ => Err("Command not found!".to_string()),
+        },
+        _ => Err("Command not found!".to_string()),
     }
 }
@@ -0,0 +1,194 @@
+use sha1::{Digest, Sha1};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use super::{
+    common::list_files,
+    data::{is_plugin_data_path, PLUGIN_DATA_FOLDER},
+    github::{get_default_branch, get_tree, list_commits, list_tags},
+};
+
+/// How many pages of 100 commits are searched for a commit with the same files
+const MAX_COMMIT_PAGES: u32 = 3;
+
+/// How many tags and commits are compared file by file if no commit has the exact same files
+const MAX_TREE_COMPARISONS: usize = 20;
+
+/// The commit a manually installed plugin was copied from
+#[derive(Debug, Clone)]
+pub struct MatchedCommit {
+    pub commit: String,
+    /// Branch the plugin should follow from now on
+    pub git_ref: String,
+    /// Tag pointing at the commit, if there is one
+    pub tag: Option<String>,
+}
+
+/// Files in a plugin folder that didn't come from its repository
+fn is_local_path(path: &str) -> bool {
+    is_plugin_data_path(path) || path.split('/').next() == Some(".git")
+}
+
+fn git_object_sha(kind: &str, contents: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{kind} {}\0", contents.len()));
+    hasher.update(contents);
+    hasher.finalize().into()
+}
+
+/// Get the SHA git gives a file's contents
+pub fn git_blob_sha(path: &Path) -> Result<String, String> {
+    let contents = fs::read(path).or(Err(format!(
+        "There was an error reading {}",
+        path.to_string_lossy()
+    )))?;
+
+    Ok(hex(&git_object_sha("blob", &contents)))
+}
+
+fn hex(sha: &[u8]) -> String {
+    sha.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Get the SHA git would give a folder as a tree, `None` if it has no files
+///
+/// Every file is taken as not executable, like cpm extracts them.
+fn git_tree_sha(path: &Path, is_root: bool) -> Result<Option<[u8; 20]>, String> {
+    let err_str = format!("There was an error reading {}", path.to_string_lossy());
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(path).or(Err(err_str.clone()))?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if is_root && (name == PLUGIN_DATA_FOLDER || name == ".git") {
+            continue;
+        }
+
+        let file_type = entry.file_type().or(Err(err_str.clone()))?;
+        if file_type.is_dir() {
+            if let Some(sha) = git_tree_sha(&entry.path(), false)? {
+                entries.push((name, true, sha));
+            }
+        } else if file_type.is_file() {
+            let contents = fs::read(entry.path()).or(Err(err_str.clone()))?;
+            entries.push((name, false, git_object_sha("blob", &contents)));
+        }
+    }
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    // git sorts folders as if their name ended with a slash
+    entries.sort_by_key(|(name, is_dir, _)| {
+        let mut key = name.as_bytes().to_vec();
+        if *is_dir {
+            key.push(b'/');
+        }
+        key
+    });
+
+    let mut contents = Vec::new();
+    for (name, is_dir, sha) in entries {
+        let mode = if is_dir { "40000" } else { "100644" };
+        contents.extend_from_slice(format!("{mode} {name}\0").as_bytes());
+        contents.extend_from_slice(&sha);
+    }
+
+    Ok(Some(git_object_sha("tree", &contents)))
+}
+
+/// Find the commit of a repository a plugin folder was copied from
+///
+/// Commits on the default branch are first compared as a whole by their tree, then tags and the
+/// newest commits file by file, which also finds plugins installed without some of the files.
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `plugin_path` path to the plugin folder in `Plugins/`
+pub fn find_matching_commit(
+    owner: &str,
+    repo: &str,
+    plugin_path: &Path,
+) -> Result<MatchedCommit, String> {
+    let mut local_files = BTreeMap::new();
+    for (path, _) in list_files(plugin_path) {
+        if !is_local_path(&path) {
+            let sha = git_blob_sha(&plugin_path.join(&path))?;
+            local_files.insert(path, sha);
+        }
+    }
+    if local_files.is_empty() {
+        return Err("The plugin folder has no files to compare".to_string());
+    }
+
+    let default_branch = get_default_branch(owner, repo)?;
+    let commits = list_commits(owner, repo, &default_branch, MAX_COMMIT_PAGES)?;
+    let tags = list_tags(owner, repo)?;
+    let tag_of = |commit: &str| {
+        tags.iter()
+            .find(|(_, sha)| sha == commit)
+            .map(|(name, _)| name.clone())
+    };
+
+    let local_tree = git_tree_sha(plugin_path, true)?.map(|sha| hex(&sha));
+    if let Some(commit) = commits
+        .iter()
+        .find(|c| Some(&c.tree) == local_tree.as_ref())
+    {
+        return Ok(MatchedCommit {
+            commit: commit.sha.clone(),
+            git_ref: default_branch,
+            tag: tag_of(&commit.sha),
+        });
+    }
+
+    // tags are compared first, a tagged commit that is also recent is only compared once
+    let mut seen = HashSet::new();
+    let candidates: Vec<String> = tags
+        .iter()
+        .take(MAX_TREE_COMPARISONS)
+        .map(|(_, sha)| sha.clone())
+        .chain(
+            commits
+                .iter()
+                .take(MAX_TREE_COMPARISONS)
+                .map(|c| c.sha.clone()),
+        )
+        .filter(|sha| seen.insert(sha.clone()))
+        .collect();
+
+    let mut closest: Option<(String, usize)> = None;
+    for commit in candidates {
+        let tree = get_tree(owner, repo, &commit)?;
+        let matching = local_files
+            .iter()
+            .filter(|(path, sha)| tree.get(*path) == Some(sha))
+            .count();
+
+        if matching == local_files.len() {
+            return Ok(MatchedCommit {
+                tag: tag_of(&commit),
+                commit,
+                git_ref: default_branch,
+            });
+        }
+        if closest.as_ref().is_none_or(|(_, m)| matching > *m) {
+            closest = Some((commit, matching));
+        }
+    }
+
+    match closest {
+        Some((commit, matching)) if matching > 0 => Err(format!(
+            "No commit of {owner}/{repo} has the same files, the closest is {} with {matching} of {} files matching",
+            commit.chars().take(7).collect::<String>(),
+            local_files.len()
+        )),
+        _ => Err(format!(
+            "No commit of {owner}/{repo} has the same files, is this the right repository?"
+        )),
+    }
+}
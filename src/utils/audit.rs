@@ -0,0 +1,233 @@
+use std::{fs, path::Path};
+use tabled::{builder::Builder, settings::Style};
+
+use super::{
+    lua::{call_string_arguments, is_global_name, list_lua_files, tokenize, Spanned, Token},
+    plugin::PLUGIN_PERMISSION_TYPES,
+};
+
+/// Modules Chatterino can't load because they are native libraries
+const NATIVE_MODULES: [&str; 10] = [
+    "socket", "lfs", "ffi", "posix", "ssl", "cjson", "lpeg", "luasql", "zlib", "bit32",
+];
+
+/// Something in a plugin's code worth knowing about before running it
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub file: String,
+    pub line: usize,
+    pub usage: String,
+    /// Permission the plugin needs in `info.json` for this to work
+    pub permission: Option<&'static str>,
+    pub note: &'static str,
+}
+
+/// Get the field accessed on a name, e.g. `open` for `io.open` or `io["open"]`, and the index of
+/// the token after it
+fn field_access(tokens: &[Spanned], i: usize) -> Option<(String, usize)> {
+    match (
+        tokens.get(i + 1).map(|t| &t.token),
+        tokens.get(i + 2).map(|t| &t.token),
+        tokens.get(i + 3).map(|t| &t.token),
+    ) {
+        (Some(Token::Symbol(".")), Some(Token::Name(field)), _) => Some((field.clone(), i + 3)),
+        (Some(Token::Symbol("[")), Some(Token::Str(field)), Some(Token::Symbol("]"))) => {
+            Some((field.clone(), i + 4))
+        }
+        _ => None,
+    }
+}
+
+/// Which permission opening a file with a mode needs
+fn io_open_permission(mode: Option<&Option<String>>) -> (Option<&'static str>, &'static str) {
+    match mode {
+        None => (Some("FilesystemRead"), "reads files"),
+        Some(Some(mode)) if mode.contains(['w', 'a', '+']) => {
+            (Some("FilesystemWrite"), "writes files")
+        }
+        Some(Some(_)) => (Some("FilesystemRead"), "reads files"),
+        Some(None) => (
+            Some("FilesystemWrite"),
+            "opens files with a mode only known at runtime",
+        ),
+    }
+}
+
+/// Find uses of files, the network, dynamic code and unavailable libraries in Lua source
+///
+/// ### Arguments
+/// * `file` path of the file in the plugin, for the findings
+/// * `tokens` tokens of the file
+pub fn audit_lua_tokens(file: &str, tokens: &[Spanned]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut add = |line, usage: String, permission, note| {
+        findings.push(Finding {
+            file: file.to_string(),
+            line,
+            usage,
+            permission,
+            note,
+        })
+    };
+
+    for (i, spanned) in tokens.iter().enumerate() {
+        let Token::Name(name) = &spanned.token else {
+            continue;
+        };
+        if !is_global_name(tokens, i) {
+            continue;
+        }
+        let line = spanned.line;
+
+        match (name.as_str(), field_access(tokens, i)) {
+            ("io", Some((field, next))) => {
+                let usage = format!("io.{field}");
+                match field.as_str() {
+                    "open" => {
+                        let arguments = call_string_arguments(tokens, next);
+                        let (permission, note) = io_open_permission(arguments.get(1));
+                        add(line, usage, permission, note);
+                    }
+                    "lines" | "input" | "read" => {
+                        add(line, usage, Some("FilesystemRead"), "reads files")
+                    }
+                    "output" | "write" => add(line, usage, Some("FilesystemWrite"), "writes files"),
+                    "popen" | "tmpfile" => add(line, usage, None, "not available in Chatterino"),
+                    _ => {}
+                }
+            }
+            ("os", Some((field, _))) => {
+                let usage = format!("os.{field}");
+                match field.as_str() {
+                    "execute" | "exit" | "getenv" | "remove" | "rename" | "tmpname" => {
+                        add(line, usage, None, "not available in Chatterino")
+                    }
+                    _ => {}
+                }
+            }
+            ("c2", Some((field, _))) if field == "HTTPRequest" => add(
+                line,
+                "c2.HTTPRequest".to_string(),
+                Some("Network"),
+                "makes HTTP requests",
+            ),
+            ("debug", Some((field, _))) => add(
+                line,
+                format!("debug.{field}"),
+                None,
+                "uses the debug library",
+            ),
+            ("package", Some((field, _))) if field == "loadlib" || field == "cpath" => add(
+                line,
+                format!("package.{field}"),
+                None,
+                "loads native libraries, not available in Chatterino",
+            ),
+            ("load" | "loadstring" | "dofile" | "loadfile", None) => {
+                add(line, name.clone(), None, "runs dynamically loaded code")
+            }
+            ("require", None) => {
+                let Some(Some(module)) = call_string_arguments(tokens, i + 1).into_iter().next()
+                else {
+                    continue;
+                };
+
+                let root = module.split('.').next().unwrap_or(&module);
+                if NATIVE_MODULES.contains(&root) {
+                    add(
+                        line,
+                        format!("require \"{module}\""),
+                        None,
+                        "native module, not available in Chatterino",
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    findings
+}
+
+/// Audit every Lua file of a plugin
+///
+/// Files that can't be tokenized are reported as findings, `cpm validate` explains why.
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder
+pub fn audit_plugin_files(plugin_path: &Path) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for file in list_lua_files(plugin_path) {
+        let source = fs::read(plugin_path.join(&file))
+            .map(|buf| String::from_utf8_lossy(&buf).into_owned())
+            .unwrap_or_default();
+
+        match tokenize(&source) {
+            Ok(tokens) => findings.extend(audit_lua_tokens(&file, &tokens)),
+            Err(error) => findings.push(Finding {
+                file: file.clone(),
+                line: error.line,
+                usage: "-".to_string(),
+                permission: None,
+                note: "could not be read, see `cpm validate`",
+            }),
+        }
+    }
+
+    findings
+}
+
+/// Permissions that are used without being declared, and declared without being used
+pub struct PermissionProblems {
+    pub missing: Vec<&'static str>,
+    pub unused: Vec<&'static str>,
+}
+
+/// Compare the permissions a plugin uses with the ones it declares
+///
+/// ### Arguments
+/// * `findings` findings of the plugin's code
+/// * `declared` permission types from `info.json`
+pub fn check_permissions(findings: &[Finding], declared: &[String]) -> PermissionProblems {
+    let mut problems = PermissionProblems {
+        missing: Vec::new(),
+        unused: Vec::new(),
+    };
+
+    for permission in PLUGIN_PERMISSION_TYPES {
+        let is_used = findings.iter().any(|f| f.permission == Some(permission));
+        let is_declared = declared.iter().any(|d| d == permission);
+
+        if is_used && !is_declared {
+            problems.missing.push(permission);
+        } else if is_declared && !is_used {
+            problems.unused.push(permission);
+        }
+    }
+
+    problems
+}
+
+pub fn print_findings(findings: &[Finding], declared: &[String]) {
+    let mut builder = Builder::default();
+    builder.push_record(["Location", "Usage", "Permission", "Note"]);
+
+    for finding in findings {
+        let permission = match finding.permission {
+            Some(permission) if declared.iter().any(|d| d == permission) => permission.to_string(),
+            Some(permission) => format!("{permission} (not declared)"),
+            None => "-".to_string(),
+        };
+
+        builder.push_record([
+            format!("{}:{}", finding.file, finding.line),
+            finding.usage.clone(),
+            permission,
+            finding.note.to_string(),
+        ]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
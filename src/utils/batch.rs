@@ -0,0 +1,91 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use tabled::{builder::Builder, settings::Style};
+
+/// What happened to one plugin of a batch
+pub struct BatchResult {
+    pub name: String,
+    /// `None` if the plugin was skipped because an earlier one failed with `--fail-fast`
+    pub result: Option<Result<String, String>>,
+}
+
+/// Run a task for every item on a bounded pool of worker threads
+///
+/// Results are returned in the order of `items`.
+///
+/// ### Arguments
+/// * `items` names of the things to work on
+/// * `jobs` how many tasks may run at the same time
+/// * `fail_fast` stop starting new tasks after the first failure
+/// * `task` work to do for one item, returning a short description of what was done
+pub fn run_batch<F>(items: &[String], jobs: usize, fail_fast: bool, task: F) -> Vec<BatchResult>
+where
+    F: Fn(&str) -> Result<String, String> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results: Mutex<Vec<Option<Result<String, String>>>> =
+        Mutex::new(items.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                if fail_fast && failed.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else {
+                    break;
+                };
+
+                let result = task(item);
+                if result.is_err() {
+                    failed.store(true, Ordering::Relaxed);
+                }
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+
+    items
+        .iter()
+        .cloned()
+        .zip(results.into_inner().unwrap())
+        .map(|(name, result)| BatchResult { name, result })
+        .collect()
+}
+
+/// Print what happened to every plugin of a batch, returning an error if any of them failed
+pub fn print_batch_results(results: &[BatchResult]) -> Result<(), String> {
+    let mut builder = Builder::default();
+    builder.push_record(["Plugin", "Status", "Details"]);
+
+    for result in results {
+        let (status, details) = match &result.result {
+            Some(Ok(details)) => ("ok", details.as_str()),
+            Some(Err(message)) => ("failed", message.as_str()),
+            None => ("skipped", "An earlier plugin failed"),
+        };
+        builder.push_record([result.name.as_str(), status, details]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+
+    let failed = results
+        .iter()
+        .filter(|r| !matches!(r.result, Some(Ok(_))))
+        .count();
+    if failed > 0 {
+        return Err(format!("{failed} of {} plugins failed", results.len()));
+    }
+
+    Ok(())
+}
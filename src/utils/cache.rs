@@ -0,0 +1,264 @@
+use pretty_duration::pretty_duration;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tabled::{builder::Builder, settings::Style};
+
+use super::{
+    common::{format_size, get_cache_path},
+    trust::SIGNATURE_EXTENSION,
+};
+
+/// A GitHub API response kept for revalidation with `If-None-Match`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub url: String,
+    pub etag: Option<String>,
+    pub body: String,
+}
+
+/// A repository tarball kept in the download cache
+#[derive(Debug)]
+pub struct CachedArchive {
+    pub owner: String,
+    pub repo: String,
+    pub commit: String,
+    pub size: u64,
+    pub age: Option<Duration>,
+}
+
+fn get_response_cache_file(url: &str, accept: &str) -> Result<PathBuf, String> {
+    let key = format!("{:x}", Sha256::digest(format!("{accept} {url}")));
+
+    Ok(get_cache_path()?.join("http").join(format!("{key}.json")))
+}
+
+/// Get the cached response for a GitHub API request
+///
+/// ### Arguments
+/// * `url` request URL
+/// * `accept` `Accept` header of the request, as it changes the response
+pub fn read_cached_response(url: &str, accept: &str) -> Option<CachedResponse> {
+    let buf = fs::read_to_string(get_response_cache_file(url, accept).ok()?).ok()?;
+
+    serde_json::from_str(&buf).ok()
+}
+
+/// Keep a GitHub API response for later revalidation and offline use
+///
+/// ### Arguments
+/// * `accept` `Accept` header of the request, as it changes the response
+/// * `response` response to keep
+pub fn write_cached_response(accept: &str, response: &CachedResponse) -> Result<(), String> {
+    let path = get_response_cache_file(&response.url, accept)?;
+    let err_str = "There was an error writing to the download cache";
+
+    fs::create_dir_all(path.parent().unwrap()).or(Err(err_str))?;
+    let buf = serde_json::to_string(response).or(Err(err_str))?;
+    fs::write(path, buf).or(Err(err_str))?;
+
+    Ok(())
+}
+
+/// Get the path an archive of a repository at a commit is cached at
+pub fn get_archive_cache_file(owner: &str, repo: &str, commit: &str) -> Result<PathBuf, String> {
+    Ok(get_cache_path()?
+        .join("archives")
+        .join(owner)
+        .join(repo)
+        .join(format!("{commit}.tar.gz")))
+}
+
+/// Get the path a release archive of a repository is cached at, next to the commit archives
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `tag` tag of the release
+/// * `asset` name of the archive, as a release can have one for each plugin in the repository
+pub fn get_release_cache_file(
+    owner: &str,
+    repo: &str,
+    tag: &str,
+    asset: &str,
+) -> Result<PathBuf, String> {
+    let asset = asset.strip_suffix(".tar.gz").unwrap_or(asset);
+
+    get_archive_cache_file(
+        owner,
+        repo,
+        &format!(
+            "release-{}-{}",
+            tag.replace('/', "_"),
+            asset.replace('/', "_")
+        ),
+    )
+}
+
+/// Get the path of the cached signature of a release archive
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `tag` tag of the release
+/// * `asset` name of the archive the signature is for
+pub fn get_release_signature_cache_file(
+    owner: &str,
+    repo: &str,
+    tag: &str,
+    asset: &str,
+) -> Result<PathBuf, String> {
+    let archive_path = get_release_cache_file(owner, repo, tag, asset)?;
+    let file_name = archive_path.file_name().unwrap().to_string_lossy();
+
+    Ok(archive_path.with_file_name(format!("{file_name}.{SIGNATURE_EXTENSION}")))
+}
+
+fn get_age(path: &Path) -> Option<Duration> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+}
+
+fn read_dir_names(path: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .map(|e| (e.file_name().to_string_lossy().to_string(), e.path()))
+        .collect();
+    names.sort();
+
+    names
+}
+
+/// Get all archives in the download cache
+pub fn list_cached_archives() -> Result<Vec<CachedArchive>, String> {
+    let archives_path = get_cache_path()?.join("archives");
+    let mut archives = Vec::new();
+
+    for (owner, owner_path) in read_dir_names(&archives_path) {
+        for (repo, repo_path) in read_dir_names(&owner_path) {
+            for (file_name, archive_path) in read_dir_names(&repo_path) {
+                let Some(commit) = file_name.strip_suffix(".tar.gz") else {
+                    continue;
+                };
+
+                archives.push(CachedArchive {
+                    owner: owner.clone(),
+                    repo: repo.clone(),
+                    commit: commit.to_string(),
+                    size: fs::metadata(&archive_path).map(|m| m.len()).unwrap_or(0),
+                    age: get_age(&archive_path),
+                });
+            }
+        }
+    }
+
+    Ok(archives)
+}
+
+/// Get the number and total size of cached GitHub API responses
+pub fn get_cached_responses_size() -> Result<(usize, u64), String> {
+    let responses = read_dir_names(&get_cache_path()?.join("http"));
+    let size = responses
+        .iter()
+        .map(|(_, path)| fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+        .sum();
+
+    Ok((responses.len(), size))
+}
+
+/// Delete everything in the download cache
+pub fn clean_cache() -> Result<(), String> {
+    let cache_path = get_cache_path()?;
+    if cache_path.is_dir() {
+        fs::remove_dir_all(cache_path).or(Err("There was an error deleting the download cache"))?;
+    }
+
+    Ok(())
+}
+
+/// Delete cached archives and API responses that haven't been written for a while
+///
+/// ### Arguments
+/// * `older_than` how old an entry has to be to be deleted
+/// * `dry_run` only count what would be deleted
+pub fn prune_cache(older_than: Duration, dry_run: bool) -> Result<(usize, u64), String> {
+    let cache_path = get_cache_path()?;
+    let mut files = Vec::new();
+
+    for (_, path) in read_dir_names(&cache_path.join("http")) {
+        files.push(path);
+    }
+    for archive in list_cached_archives()? {
+        let archive_path = get_archive_cache_file(&archive.owner, &archive.repo, &archive.commit)?;
+
+        // signatures of release archives go with them
+        let file_name = archive_path.file_name().unwrap().to_string_lossy();
+        let signature_path =
+            archive_path.with_file_name(format!("{file_name}.{SIGNATURE_EXTENSION}"));
+        if signature_path.is_file() {
+            files.push(signature_path);
+        }
+        files.push(archive_path);
+    }
+
+    let mut removed = 0;
+    let mut freed = 0;
+    for path in files {
+        if get_age(&path).is_some_and(|age| age > older_than) {
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            if dry_run || fs::remove_file(&path).is_ok() {
+                removed += 1;
+                freed += size;
+            }
+        }
+    }
+
+    if dry_run {
+        return Ok((removed, freed));
+    }
+
+    // leave no empty owner/repo folders behind
+    for (_, owner_path) in read_dir_names(&cache_path.join("archives")) {
+        for (_, repo_path) in read_dir_names(&owner_path) {
+            let _ = fs::remove_dir(repo_path);
+        }
+        let _ = fs::remove_dir(owner_path);
+    }
+
+    Ok((removed, freed))
+}
+
+pub fn print_cached_archives(archives: Vec<CachedArchive>) {
+    let mut builder = Builder::default();
+    builder.push_record(["Source", "Commit", "Size", "Cached"]);
+
+    for archive in archives {
+        builder.push_record([
+            format!("{}/{}", archive.owner, archive.repo),
+            archive.commit.chars().take(12).collect(),
+            format_size(archive.size),
+            archive
+                .age
+                .map(|age| {
+                    format!(
+                        "{} ago",
+                        pretty_duration(&Duration::from_secs(age.as_secs()), None)
+                    )
+                })
+                .unwrap_or("Unknown".to_string()),
+        ]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
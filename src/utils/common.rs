@@ -1,22 +1,26 @@
-use std::{env::var_os, io::Read, path::PathBuf};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    env::{self, var_os},
+    fs::{self, File},
+    io::{self, stdin, stdout, IsTerminal, Read, Write},
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
 
 use flate2::read::GzDecoder;
 use serde_json::{Map, Value};
 use tar::Archive;
 
+use super::progress::Progress;
+
 #[derive(Debug)]
 pub struct ProjectPath {
     pub path_components: Vec<String>,
     pub is_dir: bool,
 }
 
-#[derive(Debug)]
-pub struct ProjectFile {
-    pub path: ProjectPath,
-    pub content: Vec<u8>,
-}
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct PluginPermission {
     pub type_: String,
 }
@@ -61,20 +65,45 @@ impl Plugin {
     }
 }
 
-/// Extract files from .tar.gz file
+impl ProjectPath {
+    pub fn to_path_string(&self) -> String {
+        self.path_components.join("/")
+    }
+}
+
+/// Go through the entries of a .tar.gz stream one at a time
+///
+/// The top-level folder GitHub puts all files in is stripped, and entries that would end up
+/// outside of the archive's folder are skipped.
 ///
 /// ### Arguments
-/// * `buf` a .tar.gz file in vec of bytes
-pub fn get_files_from_gzip(buf: &Vec<u8>) -> Vec<ProjectFile> {
-    let dec = GzDecoder::new(&buf[..]);
+/// * `reader` a .tar.gz stream
+/// * `f` called with the path, size and contents of every file and folder
+fn for_each_archive_entry(
+    reader: impl Read,
+    mut f: impl FnMut(ProjectPath, u64, &mut dyn Read) -> Result<(), String>,
+) -> Result<(), String> {
+    let dec = GzDecoder::new(reader);
     let mut archive = Archive::new(dec);
-    let mut files = vec![];
+    let read_err_str = "There was an error reading the tarball";
+
+    for file in archive.entries().or(Err(read_err_str))? {
+        let mut file = file.or(Err(read_err_str))?;
 
-    for file in archive.entries().unwrap() {
-        let mut file = file.unwrap();
+        let entry_type = file.header().entry_type();
+        if !entry_type.is_dir() && !entry_type.is_file() {
+            continue;
+        }
 
-        let full_path = file.path().unwrap();
-        let is_dir = file.header().entry_type().is_dir();
+        let full_path = file.path().or(Err(read_err_str))?.into_owned();
+
+        // never write outside of the target folder
+        if full_path
+            .components()
+            .any(|comp| !matches!(comp, Component::Normal(_)))
+        {
+            continue;
+        }
 
         let mut full_path_components = full_path
             .components()
@@ -83,55 +112,491 @@ pub fn get_files_from_gzip(buf: &Vec<u8>) -> Vec<ProjectFile> {
 
         full_path_components.remove(0);
 
-        if full_path_components.len() == 0 {
+        if full_path_components.is_empty() {
             continue;
         }
 
         let project_path = ProjectPath {
-            is_dir: is_dir,
+            is_dir: entry_type.is_dir(),
             path_components: full_path_components,
         };
+        let size = file.size();
+        f(project_path, size, &mut file)?;
+    }
+
+    Ok(())
+}
+
+/// Extract files from a .tar.gz stream into a folder, one entry at a time
+///
+/// The top-level folder GitHub puts all files in is stripped. Folders are only created for the
+/// files extracted into them. Returns how many files were left out.
+///
+/// ### Arguments
+/// * `reader` a .tar.gz stream
+/// * `target_path` folder to extract into
+/// * `is_kept` whether a file, by its `/` separated path, is extracted
+/// * `progress` progress to report extracted files to
+pub fn extract_archive(
+    reader: impl Read,
+    target_path: &Path,
+    is_kept: impl Fn(&str) -> bool,
+    progress: &mut Progress,
+) -> Result<usize, String> {
+    fs::create_dir_all(target_path).or(Err(format!(
+        "There was an error creating {}",
+        target_path.to_string_lossy()
+    )))?;
+
+    let mut left_out = 0;
+    for_each_archive_entry(reader, |project_path, _, contents| {
+        let subpath = project_path.to_path_string();
+        if project_path.is_dir {
+            return Ok(());
+        }
+        if !is_kept(&subpath) {
+            left_out += 1;
+            return Ok(());
+        }
+
+        let path = target_path.join(&subpath);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).or(Err(format!("There was an error creating {subpath}")))?;
+        }
+
+        let mut f =
+            File::create_new(&path).or(Err(format!("There was an error creating {subpath}")))?;
+        io::copy(contents, &mut f).or(Err(format!("There was an writing to {subpath}")))?;
+        progress.extracted(&subpath);
+
+        Ok(())
+    })?;
+
+    Ok(left_out)
+}
+
+/// Read the files in a .tar.gz stream that match into memory
+///
+/// ### Arguments
+/// * `reader` a .tar.gz stream
+/// * `is_read` whether a file, by its `/` separated path, is read
+fn read_archive_matching(
+    reader: impl Read,
+    is_read: impl Fn(&str) -> bool,
+) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let mut files = BTreeMap::new();
+
+    for_each_archive_entry(reader, |project_path, size, contents| {
+        let subpath = project_path.to_path_string();
+        if !project_path.is_dir && is_read(&subpath) {
+            let mut buf = Vec::with_capacity(size as usize);
+            contents
+                .read_to_end(&mut buf)
+                .or(Err(format!("There was an error reading {subpath}")))?;
+            files.insert(subpath, buf);
+        }
+        Ok(())
+    })?;
+
+    Ok(files)
+}
 
-        let mut file_content: Vec<u8> = Vec::new();
-        file.read_to_end(&mut file_content).unwrap();
+/// Read all files in a .tar.gz stream into memory
+///
+/// ### Arguments
+/// * `reader` a .tar.gz stream
+pub fn read_archive(reader: impl Read) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    read_archive_matching(reader, |_| true)
+}
+
+/// Read some files in a .tar.gz stream into memory, skipping the rest
+///
+/// ### Arguments
+/// * `reader` a .tar.gz stream
+/// * `names` `/` separated paths of the files to read
+pub fn read_archive_files(
+    reader: impl Read,
+    names: &[&str],
+) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    read_archive_matching(reader, |path| names.contains(&path))
+}
 
-        files.push(ProjectFile {
-            path: project_path,
-            content: file_content,
-        });
+/// List all files in a folder with their sizes, as `/` separated paths relative to the folder
+///
+/// ### Arguments
+/// * `path` folder to list
+pub fn list_files(path: &Path) -> Vec<(String, u64)> {
+    let mut files = Vec::new();
+    let Ok(entries) = fs::read_dir(path) else {
+        return files;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => {
+                for (subpath, size) in list_files(&entry.path()) {
+                    files.push((format!("{name}/{subpath}"), size));
+                }
+            }
+            Ok(_) => files.push((name, entry.metadata().map(|m| m.len()).unwrap_or(0))),
+            Err(_) => {}
+        }
     }
 
+    files.sort();
     files
 }
 
-/// Get the default Chatterino path based on OS
+#[derive(Debug, Clone)]
+pub struct ChatterinoPathCandidate {
+    pub kind: &'static str,
+    pub path: PathBuf,
+}
+
+/// Name of the Chatterino executable
+const CHATTERINO_EXECUTABLE: &str = if cfg!(target_os = "windows") {
+    "chatterino.exe"
+} else {
+    "chatterino"
+};
+
+/// Get the folders Chatterino may be installed in, the folder of a `chatterino` on `PATH` first
+fn get_chatterino_install_folders() -> Vec<PathBuf> {
+    let mut folders: Vec<PathBuf> = var_os("PATH")
+        .map(|path| {
+            env::split_paths(&path)
+                .filter(|folder| folder.join(CHATTERINO_EXECUTABLE).is_file())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if cfg!(target_os = "windows") {
+        for (var, subfolder) in [
+            ("ProgramFiles", "Chatterino2"),
+            ("LOCALAPPDATA", "Programs/Chatterino2"),
+        ] {
+            if let Some(base) = var_os(var).map(PathBuf::from) {
+                folders.push(base.join(subfolder));
+            }
+        }
+    } else if cfg!(target_os = "macos") {
+        folders.push(PathBuf::from("/Applications/chatterino.app/Contents/MacOS"));
+    }
+
+    folders
+}
+
+/// Whether a Chatterino install folder runs in portable mode, set by a `modes` file next to the
+/// executable
+fn is_portable_install(install_folder: &Path) -> bool {
+    fs::read_to_string(install_folder.join("modes"))
+        .is_ok_and(|modes| modes.lines().any(|l| l.trim() == "portable"))
+}
+
+/// Get the folders Chatterino may keep its data in, most specific first
+pub fn get_chatterino_path_candidates() -> Vec<ChatterinoPathCandidate> {
+    let mut candidates = Vec::new();
+    let home = var_os("HOME").map(PathBuf::from);
+
+    // portable installs keep everything next to the executable
+    for install_folder in get_chatterino_install_folders() {
+        if is_portable_install(&install_folder) {
+            candidates.push(ChatterinoPathCandidate {
+                kind: "portable",
+                path: install_folder,
+            });
+        }
+    }
+
+    if cfg!(target_os = "windows") {
+        if let Some(appdata) = var_os("APPDATA").map(PathBuf::from) {
+            candidates.push(ChatterinoPathCandidate {
+                kind: "default",
+                path: appdata.join("Chatterino2"),
+            });
+        }
+    } else if cfg!(target_os = "macos") {
+        if let Some(home) = &home {
+            candidates.push(ChatterinoPathCandidate {
+                kind: "default",
+                path: home.join("Library/Application Support/chatterino"),
+            });
+        }
+    } else if cfg!(target_os = "linux") {
+        if let Some(xdg_data_home) = var_os("XDG_DATA_HOME").filter(|v| !v.is_empty()) {
+            candidates.push(ChatterinoPathCandidate {
+                kind: "XDG_DATA_HOME",
+                path: PathBuf::from(xdg_data_home).join("chatterino"),
+            });
+        }
+        if let Some(home) = &home {
+            candidates.push(ChatterinoPathCandidate {
+                kind: "default",
+                path: home.join(".local/share/chatterino"),
+            });
+            candidates.push(ChatterinoPathCandidate {
+                kind: "Flatpak",
+                path: home.join(".var/app/com.chatterino.chatterino/data/chatterino"),
+            });
+        }
+    }
+
+    // $XDG_DATA_HOME is often set to the default location
+    let mut seen = Vec::new();
+    candidates.retain(|c| {
+        let is_new = !seen.contains(&c.path);
+        seen.push(c.path.clone());
+        is_new
+    });
+
+    candidates
+}
+
+/// Get the default Chatterino path, asking the user if more than one is found
 pub fn get_default_chatterino_path() -> Result<PathBuf, String> {
-    let machine_kind = if cfg!(target_os = "linux") {
-        Some("linux")
-    } else if cfg!(target_os = "windows") {
-        Some("windows")
+    let candidates = get_chatterino_path_candidates();
+    if candidates.is_empty() {
+        return Err(
+            "Unsupported OS, cannot locate Chatterino folder. Please use --path instead."
+                .to_string(),
+        );
+    }
+
+    let found: Vec<&ChatterinoPathCandidate> =
+        candidates.iter().filter(|c| c.path.is_dir()).collect();
+
+    match found.len() {
+        0 => {
+            let looked_in = candidates
+                .iter()
+                .map(|c| format!("  {} ({})", c.path.to_string_lossy(), c.kind))
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            Err(format!(
+                "Could not find the Chatterino folder, looked in:\n{looked_in}\nPlease use --path instead."
+            ))
+        }
+        1 => Ok(found[0].path.clone()),
+        _ => choose_chatterino_path(found),
+    }
+}
+
+fn choose_chatterino_path(found: Vec<&ChatterinoPathCandidate>) -> Result<PathBuf, String> {
+    let mut found_list = String::new();
+    for (i, candidate) in found.iter().enumerate() {
+        found_list.push_str(&format!(
+            "  {}) {} ({})\n",
+            i + 1,
+            candidate.path.to_string_lossy(),
+            candidate.kind
+        ));
+    }
+
+    if !stdin().is_terminal() {
+        return Err(format!(
+            "Found more than one Chatterino folder:\n{found_list}Please use --path to choose one."
+        ));
+    }
+
+    println!("Found more than one Chatterino folder:\n{found_list}");
+    loop {
+        print!("Which one should be used? [1-{}] ", found.len());
+        stdout().flush().or(Err("Could not write to stdout"))?;
+
+        let mut answer = String::new();
+        if stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+            return Err("No Chatterino folder chosen".to_string());
+        }
+
+        match answer.trim().parse::<usize>() {
+            Ok(i) if i >= 1 && i <= found.len() => return Ok(found[i - 1].path.clone()),
+            _ => continue,
+        }
+    }
+}
+
+/// Ask for a line of input, the default is used when nothing is typed or there is no terminal
+///
+/// ### Arguments
+/// * `question` what to ask
+/// * `default` answer used when nothing is typed
+pub fn prompt(question: &str, default: &str) -> Result<String, String> {
+    if !stdin().is_terminal() {
+        return Ok(default.to_string());
+    }
+
+    if default.is_empty() {
+        print!("{question}: ");
+    } else {
+        print!("{question} [{default}]: ");
+    }
+    stdout().flush().or(Err("Could not write to stdout"))?;
+
+    let mut answer = String::new();
+    stdin()
+        .read_line(&mut answer)
+        .or(Err("Could not read from stdin"))?;
+
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(default.to_string());
+    }
+
+    Ok(answer.to_string())
+}
+
+/// Get the folder cpm keeps its own state in (plugin provenance, kept plugin data, staging)
+///
+/// ### Arguments
+/// * `chatterino_path` path to Chatterino folder
+pub fn get_cpm_state_path(chatterino_path: &Path) -> PathBuf {
+    chatterino_path.join("cpm")
+}
+
+/// Format a number of bytes for humans
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} {}", units[unit])
+    } else {
+        format!("{size:.1} {}", units[unit])
+    }
+}
+
+/// Get the folder cpm caches downloads in based on OS
+pub fn get_cache_path() -> Result<PathBuf, String> {
+    let cache_dir = if cfg!(target_os = "windows") {
+        var_os("LOCALAPPDATA")
+            .map(PathBuf::from)
+            .ok_or("Could not read %LOCALAPPDATA% environment variable.")?
+    } else if let Some(xdg_cache_home) = var_os("XDG_CACHE_HOME").filter(|v| !v.is_empty()) {
+        PathBuf::from(xdg_cache_home)
     } else {
-        None
+        let home = var_os("HOME")
+            .map(PathBuf::from)
+            .ok_or("Could not read $HOME environment variable.")?;
+
+        if cfg!(target_os = "macos") {
+            home.join("Library/Caches")
+        } else {
+            home.join(".cache")
+        }
     };
 
-    match machine_kind {
-        Some("windows") => {
-            let mut roaming_path_buf = var_os("APPDATA").map(PathBuf::from).ok_or(
-                "Could not read %APPDATA% environment variable. Please use --path instead.",
-            )?;
-            roaming_path_buf.push("Chatterino2");
-            Ok(roaming_path_buf.to_owned())
-        }
-        Some("linux") => {
-            let mut home_path_buf = var_os("HOME")
-                .map(PathBuf::from)
-                .ok_or("Could not read $HOME environment variable. Please use --path instead.")?;
-            home_path_buf.push(".local/share/chatterino");
-            Ok(home_path_buf.to_owned())
-        }
-        _ => Err(
-            "Unsupported OS, cannot locate Chatterino folder. Please use --path instead."
-                .to_string(),
-        ),
+    Ok(cache_dir.join("cpm"))
+}
+
+/// Compare two version strings like semver does, falling back to text for non-numeric parts
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| -> (Vec<String>, Option<String>) {
+        let v = v.trim_start_matches('v');
+        let v = v.split('+').next().unwrap_or(v);
+        let (release, pre_release) = match v.split_once('-') {
+            Some((release, pre_release)) => (release, Some(pre_release.to_string())),
+            None => (v, None),
+        };
+
+        (
+            release.split('.').map(|c| c.to_string()).collect(),
+            pre_release,
+        )
+    };
+
+    let (a_release, a_pre_release) = split(a);
+    let (b_release, b_pre_release) = split(b);
+
+    for i in 0..a_release.len().max(b_release.len()) {
+        let a = a_release.get(i).map(|c| c.as_str()).unwrap_or("0");
+        let b = b_release.get(i).map(|c| c.as_str()).unwrap_or("0");
+
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    // a pre-release comes before its release
+    match (a_pre_release, b_pre_release) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => a.cmp(&b),
+    }
+}
+
+/// Parse a duration like `30d`, `12h`, `15m`, `30s` or `2w`
+pub fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let err_str = format!("Invalid duration '{duration}', use e.g. 30d, 12h or 15m");
+
+    let split_at = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or(err_str.clone())?;
+    let (amount, unit) = duration.split_at(split_at);
+    let amount: u64 = amount.parse().or(Err(err_str.clone()))?;
+
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return Err(err_str),
+    };
+
+    let seconds = amount.checked_mul(seconds).ok_or(err_str)?;
+    Ok(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        for (duration, seconds) in [
+            ("30s", 30),
+            ("15m", 15 * 60),
+            ("12h", 12 * 60 * 60),
+            ("30d", 30 * 24 * 60 * 60),
+            ("2w", 2 * 7 * 24 * 60 * 60),
+            ("0d", 0),
+        ] {
+            assert_eq!(
+                parse_duration(duration),
+                Ok(Duration::from_secs(seconds)),
+                "{duration}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for duration in [
+            "",
+            "30",
+            "d",
+            "-1d",
+            "1.5h",
+            "30y",
+            "30 d",
+            "18446744073709551615w",
+            "30500000000000000w",
+            "99999999999999999999s",
+        ] {
+            assert!(parse_duration(duration).is_err(), "{duration}");
+        }
     }
 }
@@ -0,0 +1,258 @@
+use std::{
+    env::var_os,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tabled::{builder::Builder, settings::Style};
+use toml::{Table, Value};
+
+use super::common::get_default_chatterino_path;
+
+/// Environment variable overriding the Chatterino folder, below `--path` and `--profile`
+pub const CHATTERINO_PATH_ENV: &str = "CPM_CHATTERINO_PATH";
+
+/// Config keys that can be set, `*` matches any single key segment
+///
+/// Trusted keys aren't listed, they are only managed with `cpm trust`.
+const CONFIG_KEYS: [&str; 6] = [
+    "default_profile",
+    // the single index from before indexes could be named, read as the `default` index
+    "index.url",
+    "indexes.*.priority",
+    "indexes.*.url",
+    "profiles.*",
+    "signatures",
+];
+
+/// Config keys whose values are always stored as strings, e.g. a profile path like `123`
+const STRING_CONFIG_KEYS: [&str; 4] = [
+    "default_profile",
+    "index.url",
+    "indexes.*.url",
+    "profiles.*",
+];
+
+/// Whether a dotted key matches a pattern from the known keys, `*` matches any single segment
+fn matches_config_key(pattern: &str, key: &str) -> bool {
+    let segments: Vec<&str> = key.split('.').collect();
+    let pattern_segments: Vec<&str> = pattern.split('.').collect();
+
+    pattern_segments.len() == segments.len()
+        && pattern_segments
+            .iter()
+            .zip(&segments)
+            .all(|(p, s)| !s.is_empty() && (*p == "*" || p == s))
+}
+
+/// Get the path of the cpm config file based on OS
+pub fn get_config_path() -> Result<PathBuf, String> {
+    let config_dir = if cfg!(target_os = "windows") {
+        var_os("APPDATA")
+            .map(PathBuf::from)
+            .ok_or("Could not read %APPDATA% environment variable.")?
+    } else if let Some(xdg_config_home) = var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        PathBuf::from(xdg_config_home)
+    } else {
+        let home = var_os("HOME")
+            .map(PathBuf::from)
+            .ok_or("Could not read $HOME environment variable.")?;
+
+        if cfg!(target_os = "macos") {
+            home.join("Library/Application Support")
+        } else {
+            home.join(".config")
+        }
+    };
+
+    Ok(config_dir.join("cpm").join("config.toml"))
+}
+
+/// Read the cpm config file, an empty config is returned if it doesn't exist yet
+pub fn read_config() -> Result<Table, String> {
+    let path = get_config_path()?;
+    if !path.is_file() {
+        return Ok(Table::new());
+    }
+
+    let buf =
+        fs::read_to_string(&path).or(Err("There was an error reading the cpm config file"))?;
+
+    buf.parse::<Table>().map_err(|e| {
+        format!(
+            "There was an error parsing the cpm config file {}: {}",
+            path.to_string_lossy(),
+            e.message()
+        )
+    })
+}
+
+/// Write the cpm config file
+pub fn write_config(config: &Table) -> Result<(), String> {
+    let path = get_config_path()?;
+    let err_str = "There was an error writing the cpm config file";
+
+    fs::create_dir_all(path.parent().unwrap()).or(Err(err_str))?;
+    let buf = toml::to_string_pretty(config).or(Err(err_str))?;
+    fs::write(path, buf).or(Err(err_str))?;
+
+    Ok(())
+}
+
+/// Check that a dotted config key is one cpm knows about
+pub fn validate_config_key(key: &str) -> Result<(), String> {
+    if !CONFIG_KEYS
+        .iter()
+        .any(|known| matches_config_key(known, key))
+    {
+        return Err(format!("Unknown config key '{key}'"));
+    }
+
+    Ok(())
+}
+
+/// Get a value from the config by dotted key, e.g. `profiles.nightly`
+pub fn get_config_value<'a>(config: &'a Table, key: &str) -> Option<&'a Value> {
+    let mut segments = key.split('.');
+    let mut value = config.get(segments.next()?)?;
+
+    for segment in segments {
+        value = value.as_table()?.get(segment)?;
+    }
+
+    Some(value)
+}
+
+/// Set a value in the config by dotted key, creating tables along the way
+pub fn set_config_value(config: &mut Table, key: &str, value: Value) -> Result<(), String> {
+    let segments: Vec<&str> = key.split('.').collect();
+    let (last, parents) = segments.split_last().unwrap();
+
+    let mut table = config;
+    for segment in parents {
+        table = table
+            .entry(segment.to_string())
+            .or_insert(Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or(format!("Config key '{segment}' is not a table"))?;
+    }
+    table.insert(last.to_string(), value);
+
+    Ok(())
+}
+
+/// Remove a value from the config by dotted key, returning whether it was set
+pub fn unset_config_value(config: &mut Table, key: &str) -> bool {
+    let segments: Vec<&str> = key.split('.').collect();
+    let (last, parents) = segments.split_last().unwrap();
+
+    let mut table = config;
+    for segment in parents {
+        match table.get_mut(*segment).and_then(|v| v.as_table_mut()) {
+            Some(t) => table = t,
+            None => return false,
+        }
+    }
+
+    table.remove(*last).is_some()
+}
+
+/// Parse a value given on the command line, anything that isn't a bool or integer is a string
+///
+/// ### Arguments
+/// * `key` dotted key the value is set for, names and paths are never parsed
+/// * `value` value as given on the command line
+pub fn parse_config_value(key: &str, value: &str) -> Value {
+    if STRING_CONFIG_KEYS
+        .iter()
+        .any(|pattern| matches_config_key(pattern, key))
+    {
+        Value::String(value.to_string())
+    } else if let Ok(b) = value.parse::<bool>() {
+        Value::Boolean(b)
+    } else if let Ok(i) = value.parse::<i64>() {
+        Value::Integer(i)
+    } else {
+        Value::String(value.to_string())
+    }
+}
+
+/// Flatten the config into dotted keys and displayable values
+pub fn flatten_config(config: &Table) -> Vec<(String, String)> {
+    fn flatten(prefix: &str, table: &Table, entries: &mut Vec<(String, String)>) {
+        for (key, value) in table {
+            let key = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{prefix}.{key}")
+            };
+
+            match value {
+                Value::Table(t) => flatten(&key, t, entries),
+                Value::String(s) => entries.push((key, s.clone())),
+                v => entries.push((key, v.to_string())),
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    flatten("", config, &mut entries);
+    entries
+}
+
+fn get_profile_path(config: &Table, profile: &str) -> Result<PathBuf, String> {
+    get_config_value(config, &format!("profiles.{profile}"))
+        .and_then(|v| v.as_str())
+        .map(PathBuf::from)
+        .ok_or(format!(
+            "Profile '{profile}' not found. Add it with `cpm config set profiles.{profile} <path>`"
+        ))
+}
+
+/// Get the Chatterino folder
+///
+/// In order of precedence: `--path`, `--profile`, the `CPM_CHATTERINO_PATH` environment
+/// variable, the default profile from the config, and finally the default for the OS.
+///
+/// ### Arguments
+/// * `chatterino_path` value of `--path`
+/// * `profile` value of `--profile`
+pub fn get_chatterino_path(
+    chatterino_path: Option<&String>,
+    profile: Option<&String>,
+) -> Result<PathBuf, String> {
+    if let Some(chatterino_path) = chatterino_path {
+        return Ok(Path::new(chatterino_path).to_owned());
+    }
+
+    if let Some(profile) = profile {
+        return get_profile_path(&read_config()?, profile);
+    }
+
+    if let Some(env_path) = var_os(CHATTERINO_PATH_ENV).filter(|v| !v.is_empty()) {
+        return Ok(PathBuf::from(env_path));
+    }
+
+    // only read now, so a broken config doesn't get in the way of --path or the environment
+    let config = read_config()?;
+    if let Some(default_profile) = get_config_value(&config, "default_profile") {
+        let default_profile = default_profile
+            .as_str()
+            .ok_or("Config key 'default_profile' must be a profile name")?;
+        return get_profile_path(&config, default_profile);
+    }
+
+    get_default_chatterino_path()
+}
+
+pub fn print_config(entries: Vec<(String, String)>) {
+    let mut builder = Builder::default();
+    builder.push_record(["Key", "Value"]);
+
+    for (key, value) in entries {
+        builder.push_record([key, value]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
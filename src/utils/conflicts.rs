@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, fs, path::Path};
+use tabled::{builder::Builder, settings::Style};
+
+use super::lua::{call_string_arguments, is_global_name, list_lua_files, tokenize, Spanned, Token};
+
+/// Commands and callbacks a plugin registers, found in its Lua sources
+#[derive(Debug, Clone, Default)]
+pub struct Registrations {
+    pub commands: Vec<String>,
+    /// Events the plugin registers callbacks for, e.g. `CompletionRequested`
+    pub callbacks: Vec<String>,
+}
+
+/// Get the function called on `c2` at a token, e.g. `register_command` for `c2.register_command`
+fn c2_function(tokens: &[Spanned], i: usize) -> Option<&str> {
+    if !matches!(&tokens[i].token, Token::Name(name) if name == "c2") || !is_global_name(tokens, i)
+    {
+        return None;
+    }
+
+    match (
+        tokens.get(i + 1).map(|t| &t.token),
+        tokens.get(i + 2).map(|t| &t.token),
+    ) {
+        (Some(Token::Symbol(".")), Some(Token::Name(function))) => Some(function),
+        _ => None,
+    }
+}
+
+/// Get the event of a `c2.register_callback(c2.EventType.<event>, ...)` call
+///
+/// ### Arguments
+/// * `tokens` tokens of the file
+/// * `start` index of the token after `register_callback`
+fn callback_event(tokens: &[Spanned], start: usize) -> Option<String> {
+    let tokens: Vec<&Token> = tokens
+        .iter()
+        .skip(start)
+        .take(6)
+        .map(|t| &t.token)
+        .collect();
+
+    match tokens.as_slice() {
+        [Token::Symbol("("), Token::Name(c2), Token::Symbol("."), Token::Name(enum_name), Token::Symbol("."), Token::Name(event)]
+            if c2 == "c2" && enum_name == "EventType" =>
+        {
+            Some(event.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Find the commands and callbacks registered with literal names in Lua source
+///
+/// ### Arguments
+/// * `tokens` tokens of the file
+/// * `registrations` registrations to add to
+pub fn find_registrations(tokens: &[Spanned], registrations: &mut Registrations) {
+    for i in 0..tokens.len() {
+        match c2_function(tokens, i) {
+            Some("register_command") => {
+                let Some(Some(name)) = call_string_arguments(tokens, i + 3).into_iter().next()
+                else {
+                    continue;
+                };
+                if !registrations.commands.contains(&name) {
+                    registrations.commands.push(name);
+                }
+            }
+            Some("register_callback") => {
+                let Some(event) = callback_event(tokens, i + 3) else {
+                    continue;
+                };
+                if !registrations.callbacks.contains(&event) {
+                    registrations.callbacks.push(event);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Find the commands and callbacks a plugin registers
+///
+/// Files that can't be tokenized are skipped, `cpm validate` reports them.
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder
+pub fn scan_registrations(plugin_path: &Path) -> Registrations {
+    let mut registrations = Registrations::default();
+
+    for file in list_lua_files(plugin_path) {
+        let Ok(buf) = fs::read(plugin_path.join(&file)) else {
+            continue;
+        };
+        if let Ok(tokens) = tokenize(&String::from_utf8_lossy(&buf)) {
+            find_registrations(&tokens, &mut registrations);
+        }
+    }
+
+    registrations
+}
+
+/// Find the commands and callbacks of all installed plugins
+///
+/// ### Arguments
+/// * `plugins_path` path to the Chatterino `Plugins` folder
+/// * `folders` folder names of the installed plugins
+pub fn scan_installed_registrations(
+    plugins_path: &Path,
+    folders: &[String],
+) -> Vec<(String, Registrations)> {
+    folders
+        .iter()
+        .map(|folder| {
+            (
+                folder.clone(),
+                scan_registrations(&plugins_path.join(folder)),
+            )
+        })
+        .collect()
+}
+
+/// Get the commands registered by more than one plugin, with the plugins that register them
+///
+/// ### Arguments
+/// * `plugins` folder names of plugins with their registrations
+pub fn find_conflicts(plugins: &[(String, Registrations)]) -> BTreeMap<String, Vec<String>> {
+    let mut owners: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (folder, registrations) in plugins {
+        for command in &registrations.commands {
+            owners
+                .entry(command.clone())
+                .or_default()
+                .push(folder.clone());
+        }
+    }
+
+    owners.retain(|_, folders| folders.len() > 1);
+    owners
+}
+
+/// Get the commands of a plugin that other plugins already register
+///
+/// ### Arguments
+/// * `folder` folder name of the plugin
+/// * `registrations` registrations of the plugin
+/// * `others` folder names of other plugins with their registrations
+pub fn find_plugin_conflicts(
+    folder: &str,
+    registrations: &Registrations,
+    others: &[(String, Registrations)],
+) -> Vec<(String, Vec<String>)> {
+    registrations
+        .commands
+        .iter()
+        .filter_map(|command| {
+            let owners: Vec<String> = others
+                .iter()
+                .filter(|(other, r)| other != folder && r.commands.contains(command))
+                .map(|(other, _)| other.clone())
+                .collect();
+            (!owners.is_empty()).then(|| (command.clone(), owners))
+        })
+        .collect()
+}
+
+pub fn print_conflicts(conflicts: &BTreeMap<String, Vec<String>>) {
+    let mut builder = Builder::default();
+    builder.push_record(["Command", "Registered By"]);
+
+    for (command, folders) in conflicts {
+        builder.push_record([command.clone(), folders.join(", ")]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
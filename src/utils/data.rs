@@ -0,0 +1,135 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Name of the folder Chatterino gives each plugin to persist its own state in
+pub const PLUGIN_DATA_FOLDER: &str = "data";
+
+#[derive(Debug)]
+pub struct StashedData {
+    pub folder: String,
+    pub size: u64,
+    pub stashed_at: Option<SystemTime>,
+}
+
+/// Whether a `/` separated path inside a plugin folder belongs to its data folder
+pub fn is_plugin_data_path(path: &str) -> bool {
+    path.split('/').next() == Some(PLUGIN_DATA_FOLDER)
+}
+
+fn stash_path(state_path: &Path) -> PathBuf {
+    state_path.join("data")
+}
+
+/// Get the total size of all files in a folder
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Move a plugin's data folder aside so a later reinstall can reuse it
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+/// * `plugin_path` path to the installed plugin folder
+/// * `folder` plugin folder name
+pub fn stash_plugin_data(
+    state_path: &Path,
+    plugin_path: &Path,
+    folder: &str,
+) -> Result<bool, String> {
+    let data_path = plugin_path.join(PLUGIN_DATA_FOLDER);
+    if !data_path.is_dir() {
+        return Ok(false);
+    }
+
+    let stash_path = stash_path(state_path);
+    let target_path = stash_path.join(folder);
+    let err_str = format!("There was an error keeping the data of {folder}");
+
+    fs::create_dir_all(&stash_path).or(Err(err_str.clone()))?;
+    if target_path.exists() {
+        // older data from a previous removal is superseded
+        fs::remove_dir_all(&target_path).or(Err(err_str.clone()))?;
+    }
+    fs::rename(&data_path, &target_path).or(Err(err_str))?;
+
+    Ok(true)
+}
+
+/// Move previously stashed data of a plugin into a new installation
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+/// * `folder` plugin folder name
+/// * `data_path` where the plugin's data folder should end up
+pub fn restore_plugin_data(
+    state_path: &Path,
+    folder: &str,
+    data_path: &Path,
+) -> Result<bool, String> {
+    let stashed_path = stash_path(state_path).join(folder);
+    if !stashed_path.is_dir() {
+        return Ok(false);
+    }
+
+    let err_str = format!("There was an error restoring the data of {folder}");
+    if data_path.exists() {
+        fs::remove_dir_all(data_path).or(Err(err_str.clone()))?;
+    }
+    fs::rename(&stashed_path, data_path).or(Err(err_str))?;
+
+    Ok(true)
+}
+
+/// Get all stashed plugin data folders
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+pub fn list_stashed_data(state_path: &Path) -> Result<Vec<StashedData>, String> {
+    let stash_path = stash_path(state_path);
+    if !stash_path.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(&stash_path).or(Err("Could not read stashed plugin data"))?;
+    let mut stashed = Vec::new();
+
+    for entry in entries.flatten() {
+        if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            continue;
+        }
+
+        stashed.push(StashedData {
+            folder: entry.file_name().to_string_lossy().to_string(),
+            size: dir_size(&entry.path()),
+            stashed_at: entry.metadata().and_then(|m| m.modified()).ok(),
+        });
+    }
+    stashed.sort_by(|a, b| a.folder.cmp(&b.folder));
+
+    Ok(stashed)
+}
+
+/// Delete the stashed data of a plugin
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+/// * `folder` plugin folder name
+pub fn remove_stashed_data(state_path: &Path, folder: &str) -> Result<(), String> {
+    fs::remove_dir_all(stash_path(state_path).join(folder)).or(Err(format!(
+        "There was an error deleting the data of {folder}"
+    )))
+}
@@ -0,0 +1,287 @@
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::Path,
+};
+
+use super::{
+    common::get_cpm_state_path,
+    link::list_links,
+    plan::is_dry_run,
+    plugin::validate_plugin_info,
+    provenance::{list_provenance, remove_provenance},
+    settings::{get_enabled_plugins, is_plugin_support_enabled, read_chatterino_settings},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+    Fixed,
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl Check {
+    fn new(status: CheckStatus, message: impl Into<String>) -> Self {
+        Check {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// Check the Chatterino folder for problems that stop plugins from being installed or loaded
+///
+/// ### Arguments
+/// * `chatterino_path` path to Chatterino folder
+/// * `fix` repair problems that can be repaired without losing anything
+pub fn run_checks(chatterino_path: &Path, fix: bool) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let path_str = chatterino_path.to_string_lossy();
+    if !chatterino_path.is_dir() {
+        checks.push(Check::new(
+            CheckStatus::Error,
+            format!("Chatterino folder {path_str} does not exist"),
+        ));
+        return checks;
+    }
+    checks.push(Check::new(
+        CheckStatus::Ok,
+        format!("Chatterino folder is {path_str}"),
+    ));
+
+    check_settings(chatterino_path, &mut checks);
+
+    let plugins_path = chatterino_path.join("Plugins");
+    if !plugins_path.is_dir() {
+        if fix && fs::create_dir_all(&plugins_path).is_ok() {
+            checks.push(Check::new(CheckStatus::Fixed, "Created Plugins/ folder"));
+        } else {
+            checks.push(Check::new(
+                CheckStatus::Error,
+                "Plugins/ folder not found, run `cpm doctor --fix` to create it",
+            ));
+            return checks;
+        }
+    }
+
+    // try writing to Plugins/ the same way an install would, a dry run only looks at permissions
+    let is_writable = if is_dry_run() {
+        fs::metadata(&plugins_path).is_ok_and(|m| !m.permissions().readonly())
+    } else {
+        let probe_path = plugins_path.join(".cpm-doctor");
+        File::create(&probe_path).is_ok() && fs::remove_file(&probe_path).is_ok()
+    };
+    if is_writable {
+        checks.push(Check::new(CheckStatus::Ok, "Plugins/ folder is writable"));
+    } else {
+        checks.push(Check::new(
+            CheckStatus::Error,
+            "Plugins/ folder is not writable",
+        ));
+    }
+
+    check_plugins(&plugins_path, &mut checks);
+    check_leftovers(chatterino_path, fix, &mut checks);
+
+    checks
+}
+
+fn check_settings(chatterino_path: &Path, checks: &mut Vec<Check>) {
+    match read_chatterino_settings(chatterino_path) {
+        Ok(Some(settings)) => {
+            if is_plugin_support_enabled(&settings) {
+                checks.push(Check::new(CheckStatus::Ok, "Plugin support is enabled"));
+            } else {
+                checks.push(Check::new(
+                    CheckStatus::Error,
+                    "Plugin support is disabled, enable it in Chatterino under Settings > Plugins",
+                ));
+            }
+        }
+        Ok(None) => checks.push(Check::new(
+            CheckStatus::Warning,
+            "Chatterino settings not found, has Chatterino been started with this folder?",
+        )),
+        Err(message) => checks.push(Check::new(CheckStatus::Error, message)),
+    }
+}
+
+fn check_plugins(plugins_path: &Path, checks: &mut Vec<Check>) {
+    let Ok(entries) = fs::read_dir(plugins_path) else {
+        checks.push(Check::new(
+            CheckStatus::Error,
+            "Could not read Plugins/ folder",
+        ));
+        return;
+    };
+
+    let enabled_plugins = read_chatterino_settings(plugins_path.parent().unwrap())
+        .ok()
+        .flatten()
+        .map(|settings| get_enabled_plugins(&settings));
+
+    let links = list_links(&get_cpm_state_path(plugins_path.parent().unwrap()));
+
+    let mut folders_by_name: HashMap<String, Vec<String>> = HashMap::new();
+    // linked plugins are symlinks to folders, so they are followed, and reported once broken
+    let mut folders: Vec<_> = entries
+        .flatten()
+        .filter(|e| {
+            let path = e.path();
+            path.is_dir() || (!path.exists() && e.file_type().is_ok_and(|t| t.is_symlink()))
+        })
+        .collect();
+    folders.sort_by_key(|e| e.file_name());
+
+    for entry in folders {
+        let folder = entry.file_name().to_string_lossy().to_string();
+        let plugin_path = entry.path();
+        let mut problems = Vec::new();
+
+        if !plugin_path.exists() {
+            let target = fs::read_link(&plugin_path)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or("its target".to_string());
+            let problem = format!("{folder} is linked to {target}, which no longer exists");
+            checks.push(Check::new(
+                CheckStatus::Error,
+                if links.contains_key(&folder) {
+                    format!("{problem}, run `cpm remove {folder}` to unlink it")
+                } else {
+                    problem
+                },
+            ));
+            continue;
+        }
+
+        match fs::read_to_string(plugin_path.join("info.json")) {
+            Ok(buf) => match serde_json::from_str::<Value>(&buf) {
+                Ok(json) => {
+                    problems.extend(validate_plugin_info(&json));
+                    if let Some(name) = json.get("name").and_then(|v| v.as_str()) {
+                        folders_by_name
+                            .entry(name.to_string())
+                            .or_default()
+                            .push(folder.clone());
+                    }
+                }
+                Err(e) => problems.push(format!("info.json is not valid JSON ({e})")),
+            },
+            Err(_) => problems.push("info.json not found".to_string()),
+        }
+
+        if !plugin_path.join("init.lua").is_file() {
+            problems.push("init.lua not found".to_string());
+        }
+
+        if problems.is_empty() {
+            checks.push(Check::new(CheckStatus::Ok, format!("{folder} looks fine")));
+        } else {
+            for problem in problems {
+                checks.push(Check::new(
+                    CheckStatus::Error,
+                    format!("{folder}: {problem}"),
+                ));
+            }
+        }
+
+        if let Some(enabled_plugins) = &enabled_plugins {
+            if !enabled_plugins.contains(&folder) {
+                checks.push(Check::new(
+                    CheckStatus::Warning,
+                    format!("{folder} is not enabled in Chatterino"),
+                ));
+            }
+        }
+    }
+
+    let mut duplicates: Vec<_> = folders_by_name
+        .into_iter()
+        .filter(|(_, folders)| folders.len() > 1)
+        .collect();
+    duplicates.sort();
+    for (name, folders) in duplicates {
+        checks.push(Check::new(
+            CheckStatus::Warning,
+            format!(
+                "Plugin name '{name}' is used by more than one plugin: {}",
+                folders.join(", ")
+            ),
+        ));
+    }
+}
+
+fn check_leftovers(chatterino_path: &Path, fix: bool, checks: &mut Vec<Check>) {
+    let state_path = get_cpm_state_path(chatterino_path);
+
+    // staging folders only outlive an install that was interrupted
+    let staging_path = state_path.join("staging");
+    if let Ok(entries) = fs::read_dir(&staging_path) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if fix && fs::remove_dir_all(entry.path()).is_ok() {
+                checks.push(Check::new(
+                    CheckStatus::Fixed,
+                    format!("Removed partial install of {name}"),
+                ));
+            } else {
+                checks.push(Check::new(
+                    CheckStatus::Warning,
+                    format!("Found partial install of {name}, run `cpm doctor --fix` to remove it"),
+                ));
+            }
+        }
+    }
+
+    let plugins_path = chatterino_path.join("Plugins");
+    for folder in list_provenance(&state_path) {
+        if plugins_path.join(&folder).is_dir() {
+            continue;
+        }
+
+        if fix && remove_provenance(&state_path, &folder).is_ok() {
+            checks.push(Check::new(
+                CheckStatus::Fixed,
+                format!("Forgot {folder}, which is no longer installed"),
+            ));
+        } else {
+            checks.push(Check::new(
+                CheckStatus::Warning,
+                format!(
+                    "cpm remembers installing {folder}, which is no longer installed, run `cpm doctor --fix` to forget it"
+                ),
+            ));
+        }
+    }
+}
+
+pub fn print_checks(checks: &[Check]) {
+    for check in checks {
+        let status = match check.status {
+            CheckStatus::Ok => "  ok ",
+            CheckStatus::Warning => " warn",
+            CheckStatus::Error => "error",
+            CheckStatus::Fixed => "fixed",
+        };
+        println!("[{status}] {}", check.message);
+    }
+
+    let problems = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Warning || c.status == CheckStatus::Error)
+        .count();
+    if problems == 0 {
+        println!("No problems found");
+    } else {
+        println!("Found {problems} problem(s)");
+    }
+}
@@ -0,0 +1,139 @@
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::Path,
+};
+
+use super::{
+    common::read_archive_files,
+    ignore::{IgnoreRules, IGNORE_FILE},
+};
+
+/// Files Chatterino needs to load a plugin, installed whatever the filters say
+const REQUIRED_FILES: [&str; 2] = ["info.json", "init.lua"];
+
+/// Which files of a plugin get installed or packed
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    /// Patterns from the plugin's `.cpmignore`
+    ignore: IgnoreRules,
+    /// Patterns from `files` in `info.json`, only matching files are kept
+    allow: Option<IgnoreRules>,
+    /// Patterns given with `--exclude`
+    exclude: IgnoreRules,
+}
+
+impl FileFilter {
+    /// Create a filter from a plugin's own files
+    ///
+    /// ### Arguments
+    /// * `cpmignore` contents of `.cpmignore`, if the plugin has one
+    /// * `info_json` contents of `info.json`, if the plugin has one
+    /// * `exclude` patterns in `.gitignore` syntax given by the user
+    pub fn new(
+        cpmignore: Option<&str>,
+        info_json: Option<&str>,
+        exclude: &[String],
+    ) -> Result<Self, String> {
+        let ignore = match cpmignore {
+            Some(contents) => {
+                IgnoreRules::parse(contents).map_err(|e| format!("{IGNORE_FILE}: {e}"))?
+            }
+            None => IgnoreRules::default(),
+        };
+
+        let allow = match info_json.and_then(|buf| serde_json::from_str::<Value>(buf).ok()) {
+            Some(json) => match json.get("files") {
+                Some(Value::Array(files)) => {
+                    let mut allow = IgnoreRules::default();
+                    for file in files {
+                        let pattern = file
+                            .as_str()
+                            .ok_or("info.json: 'files' is not a list of strings")?;
+                        allow
+                            .add(pattern)
+                            .map_err(|e| format!("info.json: 'files' has an {e}"))?;
+                    }
+                    Some(allow)
+                }
+                Some(_) => return Err("info.json: 'files' is not a list of strings".to_string()),
+                None => None,
+            },
+            None => None,
+        };
+
+        let mut exclude_rules = IgnoreRules::default();
+        for pattern in exclude {
+            exclude_rules
+                .add(pattern)
+                .map_err(|e| format!("--exclude: {e}"))?;
+        }
+
+        Ok(FileFilter {
+            ignore,
+            allow,
+            exclude: exclude_rules,
+        })
+    }
+
+    /// Create a filter from the `.cpmignore` and `info.json` in a plugin folder
+    ///
+    /// ### Arguments
+    /// * `plugin_path` path to the plugin folder
+    /// * `exclude` patterns in `.gitignore` syntax given by the user
+    pub fn read(plugin_path: &Path, exclude: &[String]) -> Result<Self, String> {
+        let cpmignore = fs::read_to_string(plugin_path.join(IGNORE_FILE)).ok();
+        let info_json = fs::read_to_string(plugin_path.join("info.json")).ok();
+
+        FileFilter::new(cpmignore.as_deref(), info_json.as_deref(), exclude)
+    }
+
+    /// Create a filter from the `.cpmignore` and `info.json` among a plugin's files
+    ///
+    /// ### Arguments
+    /// * `files` contents of the plugin's files by `/` separated path
+    /// * `exclude` patterns in `.gitignore` syntax given by the user
+    pub fn from_files(
+        files: &BTreeMap<String, Vec<u8>>,
+        exclude: &[String],
+    ) -> Result<Self, String> {
+        let read_text = |file: &str| files.get(file).map(|c| String::from_utf8_lossy(c));
+
+        FileFilter::new(
+            read_text(IGNORE_FILE).as_deref(),
+            read_text("info.json").as_deref(),
+            exclude,
+        )
+    }
+
+    /// Create a filter from the `.cpmignore` and `info.json` in a plugin's .tar.gz archive
+    ///
+    /// ### Arguments
+    /// * `archive_path` path to the archive
+    /// * `exclude` patterns in `.gitignore` syntax given by the user
+    pub fn from_archive(archive_path: &Path, exclude: &[String]) -> Result<Self, String> {
+        let archive = File::open(archive_path).or(Err(format!(
+            "There was an error reading {}",
+            archive_path.to_string_lossy()
+        )))?;
+        let files = read_archive_files(archive, &[IGNORE_FILE, "info.json"])?;
+
+        FileFilter::from_files(&files, exclude)
+    }
+
+    /// Whether a file is kept
+    ///
+    /// ### Arguments
+    /// * `path` `/` separated path of the file in the plugin
+    pub fn is_kept(&self, path: &str) -> bool {
+        if REQUIRED_FILES.contains(&path) {
+            return true;
+        }
+
+        // the allowlist matches folders too, so listing `lib` keeps everything in it
+        self.allow.as_ref().is_none_or(|a| a.is_ignored(path))
+            && !self.ignore.is_ignored(path)
+            && !self.exclude.is_ignored(path)
+    }
+}
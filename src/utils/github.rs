@@ -1,6 +1,33 @@
 use pretty_duration::pretty_duration;
-use reqwest::{blocking::Response, header::HeaderValue};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use regex::Regex;
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    header::HeaderValue,
+    StatusCode,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use url::{Host::Domain, Url};
+
+use super::{
+    cache::{
+        get_archive_cache_file, get_release_cache_file, get_release_signature_cache_file,
+        read_cached_response, write_cached_response, CachedResponse,
+    },
+    pack::check_sha256_sidecar,
+    progress::{print_line, wait_with_countdown, Progress},
+    trust::SIGNATURE_EXTENSION,
+};
+
+use crate::VERSION_STR;
 
 pub fn handle_github_rate_limit(response: &Response) -> Result<(), String> {
     let status = response.status();
@@ -22,12 +49,13 @@ pub fn handle_github_rate_limit(response: &Response) -> Result<(), String> {
 
         let mut error_str = "GitHub API rate limit reached!".to_owned();
         let duration_str = pretty_duration(
-            &Duration::from_secs((reset_epoch - current_epoch) as u64),
+            &Duration::from_secs((reset_epoch - current_epoch).max(0) as u64),
             None,
         );
         if reset_epoch != -1 {
-            error_str.push_str(&format!(" Resets in {}", duration_str));
+            error_str.push_str(&format!(" Resets in {}.", duration_str));
         }
+        error_str.push_str(" Use --wait-for-rate-limit to wait for it instead.");
 
         return Err(error_str);
     } else if !status.is_success() {
@@ -39,3 +67,718 @@ pub fn handle_github_rate_limit(response: &Response) -> Result<(), String> {
 
     Ok(())
 }
+
+/// Extract the owner and repository name from a GitHub repository URL
+///
+/// ### Arguments
+/// * `url` GitHub repository URL, e.g. `https://github.com/owner/repo`
+pub fn parse_github_url(url: &str) -> Result<(String, String), String> {
+    // parse url
+    let parsed_url = Url::parse(url).or(Err("Invalid URL".to_string()))?;
+
+    // check if domain is github.com
+    let domain = parsed_url.host().ok_or("Could not parse domain")?;
+    if domain != Domain("github.com") {
+        return Err("Invalid GitHub repository URL".to_string());
+    }
+
+    // extract owner and repo name from path
+    let github_path_re = Regex::new(r"^/([^/]+)/([^/]+)/?$").unwrap();
+    let captures = github_path_re
+        .captures(parsed_url.path())
+        .ok_or("Invalid GitHub repository URL")?;
+
+    let owner = captures
+        .get(1)
+        .ok_or("Could not parse repository owner from URL")?
+        .as_str();
+
+    let repo = captures
+        .get(2)
+        .ok_or("Could not parse repository name from URL")?
+        .as_str();
+
+    Ok((owner.to_string(), repo.to_string()))
+}
+
+/// How often a request is retried after a network error or a server error
+const MAX_RETRIES: u32 = 3;
+
+/// Longest `Retry-After` that is waited for without `--wait-for-rate-limit`
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// How often a request waits for a rate limit before the rate limited response is returned
+const MAX_RATE_LIMIT_WAITS: u32 = 3;
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static WAIT_FOR_RATE_LIMIT: AtomicBool = AtomicBool::new(false);
+
+/// Only use the download cache instead of GitHub, for every request from now on
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Wait for GitHub rate limits to reset instead of failing, for every request from now on
+pub fn set_wait_for_rate_limit(wait: bool) {
+    WAIT_FOR_RATE_LIMIT.store(wait, Ordering::Relaxed);
+}
+
+fn get_header_u64(response: &Response, name: &str) -> Option<u64> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+}
+
+/// How long to wait before retrying a rate limited response
+///
+/// Returns the wait and whether it came from `Retry-After` (secondary rate limits), or `None` if
+/// the response isn't rate limited.
+fn get_rate_limit_wait(response: &Response) -> Option<(Duration, bool)> {
+    let status = response.status();
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    if let Some(retry_after) = get_header_u64(response, "Retry-After") {
+        return Some((Duration::from_secs(retry_after), true));
+    }
+
+    if get_header_u64(response, "X-RateLimit-Remaining") == Some(0) {
+        let reset_epoch = get_header_u64(response, "X-RateLimit-Reset")?;
+        let current_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        // the reset time is only accurate to the second
+        return Some((
+            Duration::from_secs(reset_epoch.saturating_sub(current_epoch) + 1),
+            false,
+        ));
+    }
+
+    None
+}
+
+/// Send a request, retrying network errors and server errors with exponential backoff
+///
+/// Secondary rate limits are waited for if GitHub asks for a short wait, and primary rate limits
+/// only with `--wait-for-rate-limit`, a few times at most. Other responses are returned as they
+/// are.
+///
+/// ### Arguments
+/// * `request` request to send
+/// * `what` what is being requested, for error messages
+pub fn send_with_retry(request: RequestBuilder, what: &str) -> Result<Response, String> {
+    let err_str = format!("There was an error getting {what}");
+    let mut retries = 0;
+    let mut rate_limit_waits = 0;
+
+    loop {
+        let attempt = request.try_clone().ok_or(err_str.clone())?;
+        let backoff = Duration::from_secs(1 << retries);
+
+        match attempt.send() {
+            Err(e) if retries < MAX_RETRIES && (e.is_connect() || e.is_timeout()) => {
+                print_line(&format!(
+                    "Could not reach the server for {what}, retrying in {}s",
+                    backoff.as_secs()
+                ));
+            }
+            Err(_) => return Err(err_str),
+            Ok(response) if response.status().is_server_error() && retries < MAX_RETRIES => {
+                print_line(&format!(
+                    "Server error {} for {what}, retrying in {}s",
+                    response.status().as_str(),
+                    backoff.as_secs()
+                ));
+            }
+            Ok(response) => {
+                let wait_for_rate_limit = WAIT_FOR_RATE_LIMIT.load(Ordering::Relaxed);
+
+                match get_rate_limit_wait(&response) {
+                    Some((wait, is_retry_after))
+                        if rate_limit_waits < MAX_RATE_LIMIT_WAITS
+                            && (wait_for_rate_limit
+                                || (is_retry_after && wait <= MAX_RETRY_AFTER)) =>
+                    {
+                        wait_with_countdown(wait, "GitHub API rate limit reached");
+                        // waiting for the rate limit isn't a failed attempt
+                        rate_limit_waits += 1;
+                        continue;
+                    }
+                    _ => return Ok(response),
+                }
+            }
+        }
+
+        sleep(backoff);
+        retries += 1;
+    }
+}
+
+fn github_request(url: &str) -> RequestBuilder {
+    let client = Client::new();
+
+    client.get(url).header(
+        "User-Agent",
+        format!("Chatterino Plugin Manager {VERSION_STR}"),
+    )
+}
+
+/// Make a GitHub API request, revalidating a cached response with its ETag
+///
+/// Returns `None` if GitHub responds with 404. In offline mode only the cached response is used.
+///
+/// ### Arguments
+/// * `url` request URL
+/// * `accept` `Accept` header of the request
+/// * `what` what is being requested, for error messages
+fn api_get(url: &str, accept: &str, what: &str) -> Result<Option<String>, String> {
+    let cached = read_cached_response(url, accept);
+
+    if is_offline() {
+        return cached
+            .map(|c| Some(c.body))
+            .ok_or(format!("Cannot get {what} while offline, it is not cached"));
+    }
+
+    let mut request = github_request(url).header("Accept", accept);
+    if let Some(etag) = cached.as_ref().and_then(|c| c.etag.as_ref()) {
+        request = request.header("If-None-Match", etag);
+    }
+
+    let response = send_with_retry(request, what)?;
+
+    // not modified, which doesn't count against the rate limit either
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some(cached) = cached {
+            // rewriting it marks it as recently used for `cpm cache prune`
+            let _ = write_cached_response(accept, &cached);
+            return Ok(Some(cached.body));
+        }
+    }
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    handle_github_rate_limit(&response)?;
+
+    let etag = response
+        .headers()
+        .get("ETag")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let body = response.text().or(Err(format!(
+        "There was an error getting {what} from GitHub"
+    )))?;
+
+    // failing to cache only costs a full request next time
+    let _ = write_cached_response(
+        accept,
+        &CachedResponse {
+            url: url.to_string(),
+            etag,
+            body: body.clone(),
+        },
+    );
+
+    Ok(Some(body))
+}
+
+#[derive(Debug, Clone)]
+pub struct RepoInfo {
+    pub default_branch: String,
+    pub stars: u64,
+    pub pushed_at: Option<String>,
+    pub archived: bool,
+    pub licence: Option<String>,
+}
+
+/// Get metadata of a GitHub repository
+pub fn get_repo_info(owner: &str, repo: &str) -> Result<RepoInfo, String> {
+    let repo_info_url = format!("https://api.github.com/repos/{owner}/{repo}");
+
+    let body = api_get(&repo_info_url, "application/json", "repository info")?
+        .ok_or(format!("GitHub repository {owner}/{repo} not found"))?;
+
+    // parse response body as json
+    let json: serde_json::Value = serde_json::from_str(&body)
+        .or(Err("There was an error parsing the GitHub API response"))?;
+
+    let default_branch = json
+        .get("default_branch")
+        .and_then(|v| v.as_str())
+        .ok_or("There was an error parsing the GitHub API response")?;
+
+    Ok(RepoInfo {
+        default_branch: default_branch.to_string(),
+        stars: json
+            .get("stargazers_count")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
+        pushed_at: json
+            .get("pushed_at")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string()),
+        archived: json
+            .get("archived")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        licence: json
+            .pointer("/license/spdx_id")
+            .and_then(|v| v.as_str())
+            .filter(|v| *v != "NOASSERTION")
+            .map(|v| v.to_string()),
+    })
+}
+
+/// Get the default branch of a GitHub repository
+pub fn get_default_branch(owner: &str, repo: &str) -> Result<String, String> {
+    Ok(get_repo_info(owner, repo)?.default_branch)
+}
+
+/// Get the contents of a file in a GitHub repository at a given ref
+pub fn get_file_contents(
+    owner: &str,
+    repo: &str,
+    git_ref: &str,
+    path: &str,
+) -> Result<Option<String>, String> {
+    let contents_url =
+        format!("https://api.github.com/repos/{owner}/{repo}/contents/{path}?ref={git_ref}");
+
+    api_get(&contents_url, "application/vnd.github.raw+json", path)
+}
+
+/// Resolve a branch, tag or commit of a GitHub repository to a full commit SHA
+pub fn get_commit_sha(owner: &str, repo: &str, git_ref: &str) -> Result<String, String> {
+    let commit_url = format!("https://api.github.com/repos/{owner}/{repo}/commits/{git_ref}");
+
+    let sha = api_get(&commit_url, "application/vnd.github.sha", "commit")?
+        .ok_or(format!("Could not find '{git_ref}' in {owner}/{repo}"))?;
+
+    Ok(sha.trim().to_string())
+}
+
+/// A commit in the history of a repository
+#[derive(Debug, Clone)]
+pub struct CommitInfo {
+    pub sha: String,
+    /// SHA of the commit's root tree, equal for commits with the same files
+    pub tree: String,
+}
+
+/// Get the newest commits on a branch, newest first
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `git_ref` branch to list the commits of
+/// * `pages` how many pages of 100 commits to get at most
+pub fn list_commits(
+    owner: &str,
+    repo: &str,
+    git_ref: &str,
+    pages: u32,
+) -> Result<Vec<CommitInfo>, String> {
+    let parse_err_str = "There was an error parsing the GitHub API response";
+    let mut commits = Vec::new();
+
+    for page in 1..=pages {
+        let url = format!(
+            "https://api.github.com/repos/{owner}/{repo}/commits?sha={git_ref}&per_page=100&page={page}"
+        );
+        let Some(body) = api_get(&url, "application/json", "commits")? else {
+            break;
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&body).or(Err(parse_err_str))?;
+        let page_commits = json.as_array().ok_or(parse_err_str)?;
+        for commit in page_commits {
+            let sha = commit["sha"].as_str().ok_or(parse_err_str)?;
+            let tree = commit["commit"]["tree"]["sha"]
+                .as_str()
+                .ok_or(parse_err_str)?;
+            commits.push(CommitInfo {
+                sha: sha.to_string(),
+                tree: tree.to_string(),
+            });
+        }
+
+        if page_commits.len() < 100 {
+            break;
+        }
+    }
+
+    Ok(commits)
+}
+
+/// Get the tags of a repository with the commits they point to, newest first
+pub fn list_tags(owner: &str, repo: &str) -> Result<Vec<(String, String)>, String> {
+    let parse_err_str = "There was an error parsing the GitHub API response";
+    let url = format!("https://api.github.com/repos/{owner}/{repo}/tags?per_page=100");
+
+    let Some(body) = api_get(&url, "application/json", "tags")? else {
+        return Ok(Vec::new());
+    };
+
+    let json: serde_json::Value = serde_json::from_str(&body).or(Err(parse_err_str))?;
+    json.as_array()
+        .ok_or(parse_err_str)?
+        .iter()
+        .map(|tag| {
+            let name = tag["name"].as_str().ok_or(parse_err_str)?;
+            let sha = tag["commit"]["sha"].as_str().ok_or(parse_err_str)?;
+            Ok((name.to_string(), sha.to_string()))
+        })
+        .collect()
+}
+
+/// Get the git blob SHA of every file in a repository at a commit
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `commit` commit to get the files of
+pub fn get_tree(owner: &str, repo: &str, commit: &str) -> Result<BTreeMap<String, String>, String> {
+    let parse_err_str = "There was an error parsing the GitHub API response";
+    let url = format!("https://api.github.com/repos/{owner}/{repo}/git/trees/{commit}?recursive=1");
+
+    let body = api_get(&url, "application/json", "file list")?
+        .ok_or(format!("Could not find commit {commit} in {owner}/{repo}"))?;
+
+    let json: serde_json::Value = serde_json::from_str(&body).or(Err(parse_err_str))?;
+    let mut files = BTreeMap::new();
+    for entry in json["tree"].as_array().ok_or(parse_err_str)? {
+        if entry["type"].as_str() != Some("blob") {
+            continue;
+        }
+
+        let path = entry["path"].as_str().ok_or(parse_err_str)?;
+        let sha = entry["sha"].as_str().ok_or(parse_err_str)?;
+        files.insert(path.to_string(), sha.to_string());
+    }
+
+    Ok(files)
+}
+
+#[derive(Debug, Clone)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub url: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub tag: String,
+    pub assets: Vec<ReleaseAsset>,
+}
+
+impl Release {
+    pub fn find_asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+/// Get a release of a repository
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `tag` tag of the release, or `None` for the latest release
+pub fn get_release(owner: &str, repo: &str, tag: Option<&str>) -> Result<Release, String> {
+    let parse_err_str = "There was an error parsing the GitHub API response";
+    let url = match tag {
+        Some(tag) => format!("https://api.github.com/repos/{owner}/{repo}/releases/tags/{tag}"),
+        None => format!("https://api.github.com/repos/{owner}/{repo}/releases/latest"),
+    };
+
+    let body = api_get(&url, "application/json", "release")?.ok_or(match tag {
+        Some(tag) => format!("{owner}/{repo} has no release {tag}"),
+        None => format!("{owner}/{repo} has no releases"),
+    })?;
+
+    let json: serde_json::Value = serde_json::from_str(&body).or(Err(parse_err_str))?;
+    let assets = json["assets"]
+        .as_array()
+        .ok_or(parse_err_str)?
+        .iter()
+        .map(|asset| {
+            Ok(ReleaseAsset {
+                name: asset["name"].as_str().ok_or(parse_err_str)?.to_string(),
+                url: asset["browser_download_url"]
+                    .as_str()
+                    .ok_or(parse_err_str)?
+                    .to_string(),
+                size: asset["size"].as_u64().unwrap_or(0),
+            })
+        })
+        .collect::<Result<Vec<_>, &str>>()?;
+
+    Ok(Release {
+        tag: json["tag_name"].as_str().ok_or(parse_err_str)?.to_string(),
+        assets,
+    })
+}
+
+/// Request a release asset, ready to be read
+fn request_release_asset(asset: &ReleaseAsset, progress: &Progress) -> Result<Response, String> {
+    if is_offline() {
+        return Err(format!("Cannot download {} while offline", asset.name));
+    }
+
+    let request = github_request(&asset.url).header("Accept", "application/octet-stream");
+    let response = send_with_retry(request, &asset.name)?;
+    handle_github_rate_limit(&response)?;
+    if !response.status().is_success() {
+        return Err(format!("There was an error downloading {}", asset.name));
+    }
+
+    progress.set_download_size(response.content_length().unwrap_or(asset.size));
+    Ok(response)
+}
+
+/// Download a small release asset into memory, like a checksum or a signature
+///
+/// ### Arguments
+/// * `asset` asset to download
+/// * `progress` progress to report downloaded bytes to
+pub fn download_release_asset(
+    asset: &ReleaseAsset,
+    progress: &Progress,
+) -> Result<Vec<u8>, String> {
+    let mut response = request_release_asset(asset, progress)?;
+
+    let mut contents = Vec::new();
+    response.read_to_end(&mut contents).or(Err(format!(
+        "There was an error downloading {}",
+        asset.name
+    )))?;
+    progress.downloaded(contents.len() as u64);
+
+    Ok(contents)
+}
+
+/// Pick the archive of a release to install, a .tar.gz with a .sha256 next to it
+///
+/// ### Arguments
+/// * `release` release to pick from
+/// * `folder` plugin folder name, archives named after it are preferred
+pub fn find_release_archive<'a>(
+    release: &'a Release,
+    folder: &str,
+) -> Result<&'a ReleaseAsset, String> {
+    let mut archives: Vec<&ReleaseAsset> = release
+        .assets
+        .iter()
+        .filter(|a| {
+            a.name.ends_with(".tar.gz")
+                && release.find_asset(&format!("{}.sha256", a.name)).is_some()
+        })
+        .collect();
+    archives.sort_by_key(|a| !a.name.starts_with(&format!("{folder}-")));
+
+    archives.first().copied().ok_or(format!(
+        "Release {} has no .tar.gz with a .sha256 next to it, was it made with `cpm pack`?",
+        release.tag
+    ))
+}
+
+/// Download the archive of a release into the download cache, checked against its sha256
+///
+/// Returns the path of the cached archive. It is streamed to disk rather than into memory, and
+/// only kept once its sha256 matches.
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `release` release the archive is attached to
+/// * `archive` archive to download
+/// * `progress` progress to report downloaded bytes to
+pub fn download_release_archive(
+    owner: &str,
+    repo: &str,
+    release: &Release,
+    archive: &ReleaseAsset,
+    progress: &Progress,
+) -> Result<PathBuf, String> {
+    let cache_path = get_release_cache_file(owner, repo, &release.tag, &archive.name)?;
+    if cache_path.is_file() {
+        return Ok(cache_path);
+    }
+
+    let sidecar = release
+        .find_asset(&format!("{}.sha256", archive.name))
+        .ok_or(format!(
+            "Release {} has no {}.sha256 to check it against",
+            release.tag, archive.name
+        ))?;
+    let sha256 = download_release_asset(sidecar, progress)?;
+
+    let response = request_release_asset(archive, progress)?;
+    let mut download = Download::new(response, cache_path.clone(), progress)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut download, &mut hasher).or(Err(format!(
+        "There was an error downloading {}",
+        archive.name
+    )))?;
+
+    let actual = format!("{:x}", hasher.finalize());
+    if let Err(message) =
+        check_sha256_sidecar(&actual, &String::from_utf8_lossy(&sha256), &archive.name)
+    {
+        download.discard();
+        return Err(message);
+    }
+    download.finish()?;
+
+    Ok(cache_path)
+}
+
+/// Get the minisign signature uploaded next to the archive of a release, using the download cache
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `release` release the archive is attached to
+/// * `archive` archive to get the signature of
+/// * `progress` progress to report downloaded bytes to
+pub fn download_release_signature(
+    owner: &str,
+    repo: &str,
+    release: &Release,
+    archive: &ReleaseAsset,
+    progress: &Progress,
+) -> Result<Option<String>, String> {
+    // signatures are checked every time, so caching one unchecked is fine
+    let cache_path = get_release_signature_cache_file(owner, repo, &release.tag, &archive.name)?;
+    if let Ok(cached) = fs::read_to_string(&cache_path) {
+        return Ok(Some(cached));
+    }
+
+    let Some(asset) = release.find_asset(&format!("{}.{SIGNATURE_EXTENSION}", archive.name)) else {
+        return Ok(None);
+    };
+    let signature = String::from_utf8_lossy(&download_release_asset(asset, progress)?).to_string();
+
+    let _ = fs::create_dir_all(cache_path.parent().unwrap())
+        .and_then(|_| fs::write(&cache_path, &signature));
+
+    Ok(Some(signature))
+}
+
+/// A download being streamed into the download cache
+///
+/// It is written to a `.part` file as it is read, and only kept once fully read.
+struct Download {
+    reader: Box<dyn Read>,
+    cache_file: File,
+    part_path: PathBuf,
+    cache_path: PathBuf,
+    progress: Progress,
+}
+
+impl Read for Download {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+
+        self.cache_file.write_all(&buf[..n])?;
+        self.progress.downloaded(n as u64);
+
+        Ok(n)
+    }
+}
+
+impl Download {
+    /// Start streaming a response into the download cache
+    ///
+    /// ### Arguments
+    /// * `response` response to read
+    /// * `cache_path` path to keep the download at once it is finished
+    /// * `progress` progress to report downloaded bytes to
+    fn new(response: Response, cache_path: PathBuf, progress: &Progress) -> Result<Self, String> {
+        let part_path = cache_path.with_extension("part");
+        let cache_file = fs::create_dir_all(cache_path.parent().unwrap())
+            .and_then(|_| File::create(&part_path))
+            .or(Err("There was an error writing to the download cache"))?;
+
+        Ok(Download {
+            reader: Box::new(response),
+            cache_file,
+            part_path,
+            cache_path,
+            progress: progress.clone(),
+        })
+    }
+
+    /// Stop a download that turned out to be bad, without keeping it in the cache
+    fn discard(self) {
+        drop(self.cache_file);
+        let _ = fs::remove_file(self.part_path);
+    }
+
+    /// Read the rest of the download and keep it in the cache
+    fn finish(mut self) -> Result<(), String> {
+        io::copy(&mut self, &mut io::sink()).or(Err("There was an error reading the download"))?;
+
+        drop(self.cache_file);
+        fs::rename(self.part_path, self.cache_path)
+            .or(Err("There was an error writing to the download cache"))?;
+
+        Ok(())
+    }
+}
+
+/// Download the tarball of a GitHub repository at a given commit into the download cache
+///
+/// Returns the path of the cached tarball, as it is read once for the plugin's filters and again
+/// to extract it.
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `commit` full commit SHA
+/// * `progress` progress to report downloaded bytes to
+pub fn download_tarball(
+    owner: &str,
+    repo: &str,
+    commit: &str,
+    progress: &Progress,
+) -> Result<PathBuf, String> {
+    let cache_path = get_archive_cache_file(owner, repo, commit)?;
+    if cache_path.is_file() {
+        return Ok(cache_path);
+    }
+
+    if is_offline() {
+        return Err(format!(
+            "Cannot download {owner}/{repo} while offline, commit {commit} is not cached"
+        ));
+    }
+
+    let repo_tarball_url = format!("https://api.github.com/repos/{owner}/{repo}/tarball/{commit}");
+
+    let request = github_request(&repo_tarball_url);
+
+    let response = send_with_retry(request, &format!("{owner}/{repo}"))?;
+    handle_github_rate_limit(&response)?;
+    // anything else would be kept in the cache as the tarball
+    if !response.status().is_success() {
+        return Err(format!("There was an error downloading {owner}/{repo}"));
+    }
+
+    if let Some(size) = response.content_length() {
+        progress.set_download_size(size);
+    }
+
+    Download::new(response, cache_path.clone(), progress)?.finish()?;
+
+    Ok(cache_path)
+}
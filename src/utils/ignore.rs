@@ -0,0 +1,179 @@
+use globset::{GlobBuilder, GlobMatcher};
+
+/// File in a plugin listing files that are left out of packs, in `.gitignore` syntax
+pub const IGNORE_FILE: &str = ".cpmignore";
+
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    matcher: GlobMatcher,
+    /// `!pattern`, includes files an earlier pattern ignored
+    negated: bool,
+    /// `pattern/`, only matches folders
+    dir_only: bool,
+}
+
+/// Patterns matched against `/` separated paths like `.gitignore` does
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreRules {
+    /// Parse patterns in `.gitignore` syntax
+    ///
+    /// ### Arguments
+    /// * `contents` one pattern per line, `#` starts a comment
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut rules = IgnoreRules::default();
+        for line in contents.lines() {
+            rules.add(line)?;
+        }
+
+        Ok(rules)
+    }
+
+    /// Add a pattern, after the existing ones
+    ///
+    /// ### Arguments
+    /// * `line` pattern in `.gitignore` syntax, comments and blank lines are skipped
+    pub fn add(&mut self, line: &str) -> Result<(), String> {
+        let mut pattern = line.trim_end();
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return Ok(());
+        }
+
+        let negated = pattern.starts_with('!');
+        if negated {
+            pattern = &pattern[1..];
+        }
+        // `\#` and `\!` escape patterns starting with those characters
+        if pattern.starts_with("\\#") || pattern.starts_with("\\!") {
+            pattern = &pattern[1..];
+        }
+
+        let dir_only = pattern.ends_with('/');
+        pattern = pattern.trim_end_matches('/');
+
+        // patterns with a slash are relative to the plugin folder, others match at any depth
+        let glob = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{pattern}"),
+        };
+
+        let matcher = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("invalid pattern '{line}': {e}"))?
+            .compile_matcher();
+
+        self.rules.push(IgnoreRule {
+            matcher,
+            negated,
+            dir_only,
+        });
+
+        Ok(())
+    }
+
+    /// Whether the last pattern matching a path ignores it
+    fn matches(&self, path: &str, is_dir: bool) -> Option<bool> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.matcher.is_match(path))
+            .map(|rule| !rule.negated)
+    }
+
+    /// Whether a file is ignored, either itself or through one of its folders
+    ///
+    /// ### Arguments
+    /// * `path` `/` separated path of the file in the plugin
+    pub fn is_ignored(&self, path: &str) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+
+        // like git, files in an ignored folder can't be included again
+        let mut end = 0;
+        while let Some(i) = path[end..].find('/') {
+            end += i;
+            if self.matches(&path[..end], true) == Some(true) {
+                return true;
+            }
+            end += 1;
+        }
+
+        self.matches(path, false) == Some(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> IgnoreRules {
+        IgnoreRules::parse(contents).unwrap()
+    }
+
+    #[test]
+    fn unanchored_patterns_match_at_any_depth() {
+        let rules = parse("*.md\n# comment\n\ntest.lua");
+        assert!(rules.is_ignored("README.md"));
+        assert!(rules.is_ignored("docs/guide.md"));
+        assert!(rules.is_ignored("lib/test.lua"));
+        assert!(!rules.is_ignored("init.lua"));
+        assert!(!rules.is_ignored("md"));
+    }
+
+    #[test]
+    fn anchored_patterns() {
+        let rules = parse("/build.lua\ndocs/*.md");
+        assert!(rules.is_ignored("build.lua"));
+        assert!(!rules.is_ignored("lib/build.lua"));
+        assert!(rules.is_ignored("docs/guide.md"));
+        assert!(!rules.is_ignored("lib/docs/guide.md"));
+        // `*` doesn't match `/`
+        assert!(!rules.is_ignored("docs/api/guide.md"));
+    }
+
+    #[test]
+    fn dir_patterns_only_match_folders() {
+        let rules = parse("tests/");
+        assert!(rules.is_ignored("tests/init_spec.lua"));
+        assert!(rules.is_ignored("lib/tests/a/b.lua"));
+        assert!(!rules.is_ignored("tests"));
+        assert!(!rules.is_ignored("lib/tests.lua"));
+    }
+
+    #[test]
+    fn negation() {
+        let rules = parse("*.lua\n!init.lua\n\\!bang.txt\n\\#hash.txt");
+        assert!(rules.is_ignored("lib/util.lua"));
+        assert!(!rules.is_ignored("init.lua"));
+        assert!(rules.is_ignored("!bang.txt"));
+        assert!(rules.is_ignored("#hash.txt"));
+
+        // the last matching pattern wins
+        let rules = parse("!init.lua\n*.lua");
+        assert!(rules.is_ignored("init.lua"));
+    }
+
+    #[test]
+    fn files_under_ignored_folders_stay_ignored() {
+        let rules = parse("docs/\n!docs/keep.md");
+        assert!(rules.is_ignored("docs/keep.md"));
+        assert!(rules.is_ignored("docs/other.md"));
+
+        // ignoring the folder's contents instead of the folder allows including files again
+        let rules = parse("/docs/*\n!/docs/keep.md");
+        assert!(!rules.is_ignored("docs/keep.md"));
+        assert!(rules.is_ignored("docs/other.md"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert!(IgnoreRules::parse("lib/[a-").is_err());
+        assert!(!IgnoreRules::default().is_ignored("anything"));
+    }
+}
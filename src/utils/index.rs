@@ -0,0 +1,584 @@
+use pretty_duration::pretty_duration;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tabled::{builder::Builder, settings::Style};
+use toml::Table;
+use url::Url;
+
+use super::{
+    common::{compare_versions, get_cache_path},
+    config::{get_config_value, read_config},
+    github::{is_offline, send_with_retry},
+    plugin::validate_plugin_folder_name,
+    progress::print_line,
+};
+
+use crate::VERSION_STR;
+
+/// How long a downloaded index is used before it is fetched again
+const INDEX_MAX_AGE: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Name given to the index configured with `index.url`
+pub const DEFAULT_INDEX_NAME: &str = "default";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexVersion {
+    pub version: String,
+    /// Git ref to install this version from, the version itself is used as a tag if not given
+    #[serde(rename = "ref", default, skip_serializing_if = "Option::is_none")]
+    pub ref_: Option<String>,
+}
+
+impl IndexVersion {
+    pub fn git_ref(&self) -> String {
+        self.ref_.clone().unwrap_or(self.version.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexPlugin {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    /// GitHub repository URL
+    pub source: String,
+    #[serde(default)]
+    pub versions: Vec<IndexVersion>,
+}
+
+impl IndexPlugin {
+    /// Get the newest version listed for the plugin
+    pub fn latest_version(&self) -> Option<&IndexVersion> {
+        self.versions
+            .iter()
+            .max_by(|a, b| compare_versions(&a.version, &b.version))
+    }
+
+    /// Get a version by name, or the newest one if no version is given
+    pub fn find_version(&self, version: Option<&str>) -> Result<Option<&IndexVersion>, String> {
+        match version {
+            Some(version) => self
+                .versions
+                .iter()
+                .find(|v| v.version == version || v.version == version.trim_start_matches('v'))
+                .map(Some)
+                .ok_or(format!(
+                    "Version {version} of '{}' not found in the plugin index.",
+                    self.name
+                )),
+            None => Ok(self.latest_version()),
+        }
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+
+        self.name.to_lowercase().contains(&query)
+            || self
+                .description
+                .as_ref()
+                .is_some_and(|d| d.to_lowercase().contains(&query))
+            || self.tags.iter().any(|t| t.to_lowercase().contains(&query))
+            || self
+                .authors
+                .iter()
+                .any(|a| a.to_lowercase().contains(&query))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    #[serde(default)]
+    pub plugins: Vec<IndexPlugin>,
+}
+
+impl Index {
+    /// Check that every plugin name can be used as a folder name in `Plugins/`
+    fn validate(&self) -> Result<(), String> {
+        for plugin in &self.plugins {
+            validate_plugin_folder_name(&plugin.name).map_err(|_| {
+                format!(
+                    "The plugin index has an invalid plugin name '{}'",
+                    plugin.name
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn find_plugin(&self, name: &str) -> Option<&IndexPlugin> {
+        self.plugins
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Find plugins matching a query in their name, description, tags or authors
+    ///
+    /// ### Arguments
+    /// * `query` text to look for, everything matches if not given
+    /// * `tags` tags the plugins must all have
+    pub fn search(&self, query: Option<&str>, tags: &[String]) -> Vec<&IndexPlugin> {
+        self.plugins
+            .iter()
+            .filter(|p| query.map(|q| p.matches(q)).unwrap_or(true))
+            .filter(|p| {
+                tags.iter()
+                    .all(|tag| p.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+            })
+            .collect()
+    }
+}
+
+/// Parse a plugin index, either JSON or TOML
+fn parse_index(buf: &str, location: &str) -> Result<Index, String> {
+    let index: Index = if location.ends_with(".toml") {
+        toml::from_str(buf).map_err(|e| {
+            format!(
+                "There was an error parsing the plugin index: {}",
+                e.message()
+            )
+        })?
+    } else {
+        // report the error of the format the index looks like it's in
+        serde_json::from_str(buf).or_else(|json_err| {
+            toml::from_str(buf).map_err(|toml_err| {
+                let message = if buf.trim_start().starts_with('{') {
+                    json_err.to_string()
+                } else {
+                    toml_err.message().to_string()
+                };
+                format!("There was an error parsing the plugin index: {message}")
+            })
+        })?
+    };
+    index.validate()?;
+
+    Ok(index)
+}
+
+/// Whether an index location is a http(s) URL, anything else is a local file
+fn is_remote_index(location: &str) -> bool {
+    Url::parse(location)
+        .map(|u| u.scheme() == "http" || u.scheme() == "https")
+        .unwrap_or(false)
+}
+
+fn fetch_index(location: &str) -> Result<Index, String> {
+    let buf = if is_remote_index(location) {
+        if is_offline() {
+            return Err(
+                "Cannot fetch the plugin index while offline, it is not cached".to_string(),
+            );
+        }
+
+        let client = Client::new();
+        let request = client.get(location).header(
+            "User-Agent",
+            format!("Chatterino Plugin Manager {VERSION_STR}"),
+        );
+        let response = send_with_retry(request, "the plugin index")?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Plugin index returned an unexpected status code: {}",
+                response.status().as_str()
+            ));
+        }
+
+        response
+            .text()
+            .or(Err("There was an error downloading the plugin index"))?
+    } else {
+        fs::read_to_string(location).or(Err(format!(
+            "There was an error reading the plugin index {location}"
+        )))?
+    };
+
+    parse_index(&buf, location)
+}
+
+/// A plugin index configured by the user
+#[derive(Debug, Clone)]
+pub struct IndexSource {
+    pub name: String,
+    pub location: String,
+    /// Indexes with a higher priority win when a plugin name is in more than one
+    pub priority: i64,
+}
+
+/// An index source together with the plugins it lists
+#[derive(Debug, Clone)]
+pub struct LoadedIndex {
+    pub source: IndexSource,
+    pub index: Index,
+}
+
+/// A plugin found in one of the indexes
+#[derive(Debug, Clone)]
+pub struct FoundPlugin<'a> {
+    pub index_name: &'a str,
+    pub plugin: &'a IndexPlugin,
+}
+
+impl FoundPlugin<'_> {
+    /// Name of the plugin qualified with its index, e.g. `community/translate`
+    pub fn qualified_name(&self) -> String {
+        format!("{}/{}", self.index_name, self.plugin.name)
+    }
+}
+
+/// Check that an index name can be used in qualified plugin names
+pub fn validate_index_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', '\\', '.', '@']) {
+        return Err(format!(
+            "Invalid index name '{name}', it can't contain '/', '\\', '.' or '@'"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Get the configured plugin indexes, highest priority first
+///
+/// ### Arguments
+/// * `config` cpm config
+pub fn get_index_sources(config: &Table) -> Vec<IndexSource> {
+    let mut sources = Vec::new();
+
+    if let Some(indexes) = get_config_value(config, "indexes").and_then(|v| v.as_table()) {
+        for (name, index) in indexes {
+            // the name is used as a cache file name, and only `cpm index add` checks it
+            if let Err(e) = validate_index_name(name) {
+                print_line(&format!("Warning: {e}, skipping it"));
+                continue;
+            }
+            let Some(location) = index.get("url").and_then(|v| v.as_str()) else {
+                continue;
+            };
+
+            sources.push(IndexSource {
+                name: name.clone(),
+                location: location.to_string(),
+                priority: index
+                    .get("priority")
+                    .and_then(|v| v.as_integer())
+                    .unwrap_or(0),
+            });
+        }
+    }
+
+    // the single index from before indexes could be named
+    if let Some(location) = get_config_value(config, "index.url").and_then(|v| v.as_str()) {
+        if !sources.iter().any(|s| s.name == DEFAULT_INDEX_NAME) {
+            sources.push(IndexSource {
+                name: DEFAULT_INDEX_NAME.to_string(),
+                location: location.to_string(),
+                priority: 0,
+            });
+        }
+    }
+
+    sources.sort_by(|a, b| b.priority.cmp(&a.priority).then(a.name.cmp(&b.name)));
+    sources
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedIndex {
+    location: String,
+    index: Index,
+}
+
+fn get_index_cache_file(name: &str) -> Result<PathBuf, String> {
+    validate_index_name(name)?;
+
+    Ok(get_cache_path()?
+        .join("indexes")
+        .join(format!("{name}.json")))
+}
+
+fn read_cached_index(cache_file: &Path, location: &str) -> Option<(Index, Duration)> {
+    let age = get_cache_age(cache_file)?;
+    let buf = fs::read_to_string(cache_file).ok()?;
+    let cached: CachedIndex = serde_json::from_str(&buf).ok()?;
+
+    // the cache is stale once the index moves, and caches from older versions aren't validated
+    if cached.location != location || cached.index.validate().is_err() {
+        return None;
+    }
+
+    Some((cached.index, age))
+}
+
+fn get_cache_age(cache_file: &Path) -> Option<Duration> {
+    fs::metadata(cache_file)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+}
+
+/// Forget the cached copy of an index
+pub fn remove_cached_index(name: &str) -> Result<(), String> {
+    let cache_file = get_index_cache_file(name)?;
+    if cache_file.is_file() {
+        fs::remove_file(cache_file).or(Err(format!(
+            "There was an error removing the cached index {name}"
+        )))?;
+    }
+
+    Ok(())
+}
+
+/// Get a plugin index, from the local cache if it is recent enough
+///
+/// The cache is also used when the index can't be fetched, so searching works offline.
+///
+/// ### Arguments
+/// * `source` index to get
+/// * `refresh` fetch the index even if the cached copy is recent
+pub fn get_index(source: &IndexSource, refresh: bool) -> Result<Index, String> {
+    let cache_file = get_index_cache_file(&source.name)?;
+    let cached = read_cached_index(&cache_file, &source.location);
+
+    // local indexes are cheap to read, so they are always up to date
+    if let Some((index, age)) = &cached {
+        let is_fresh = !refresh && age < &INDEX_MAX_AGE;
+        if is_remote_index(&source.location) && (is_offline() || is_fresh) {
+            return Ok(index.clone());
+        }
+    }
+
+    match fetch_index(&source.location) {
+        Ok(index) => {
+            // failing to cache only costs a download next time
+            if fs::create_dir_all(cache_file.parent().unwrap()).is_ok() {
+                let cached = CachedIndex {
+                    location: source.location.clone(),
+                    index: index.clone(),
+                };
+                if let Ok(buf) = serde_json::to_string(&cached) {
+                    let _ = fs::write(&cache_file, buf);
+                }
+            }
+            Ok(index)
+        }
+        Err(message) => match cached {
+            Some((index, _)) => {
+                println!(
+                    "Warning: {message}, using the cached copy of index {}",
+                    source.name
+                );
+                Ok(index)
+            }
+            None => Err(format!("{message} ({})", source.name)),
+        },
+    }
+}
+
+/// Get all configured plugin indexes, highest priority first
+///
+/// Indexes that can't be read are skipped with a warning, as long as at least one can.
+pub fn get_indexes() -> Result<Vec<LoadedIndex>, String> {
+    let sources = get_index_sources(&read_config()?);
+    if sources.is_empty() {
+        return Err(
+            "No plugin index configured. Add one with `cpm index add <name> <url|path>`"
+                .to_string(),
+        );
+    }
+
+    let mut indexes = Vec::new();
+    let mut last_error = String::new();
+    for source in sources {
+        match get_index(&source, false) {
+            Ok(index) => indexes.push(LoadedIndex { source, index }),
+            Err(message) => {
+                println!("Warning: {message}");
+                last_error = message;
+            }
+        }
+    }
+
+    if indexes.is_empty() {
+        return Err(last_error);
+    }
+
+    Ok(indexes)
+}
+
+/// Find a plugin by name in the indexes, the highest priority index wins
+///
+/// ### Arguments
+/// * `indexes` loaded indexes, highest priority first
+/// * `name` plugin name, optionally qualified with an index name (`index/plugin`)
+pub fn find_index_plugin<'a>(
+    indexes: &'a [LoadedIndex],
+    name: &str,
+) -> Result<FoundPlugin<'a>, String> {
+    let (index_name, plugin_name) = match name.split_once('/') {
+        Some((index_name, plugin_name)) => (Some(index_name), plugin_name),
+        None => (None, name),
+    };
+
+    if let Some(index_name) = index_name {
+        if !indexes.iter().any(|i| i.source.name == index_name) {
+            return Err(format!("Plugin index '{index_name}' not found."));
+        }
+    }
+
+    let found: Vec<FoundPlugin> = indexes
+        .iter()
+        .filter(|i| index_name.map(|n| i.source.name == n).unwrap_or(true))
+        .filter_map(|i| {
+            i.index.find_plugin(plugin_name).map(|plugin| FoundPlugin {
+                index_name: &i.source.name,
+                plugin,
+            })
+        })
+        .collect();
+
+    let Some(first) = found.first() else {
+        return Err(format!("Plugin '{name}' not found in the plugin index."));
+    };
+
+    if found.len() > 1 {
+        let others = found[1..]
+            .iter()
+            .map(|f| f.qualified_name())
+            .collect::<Vec<String>>()
+            .join(", ");
+        print_line(&format!(
+            "Using {}, also found {others}. Use a qualified name to choose another one.",
+            first.qualified_name()
+        ));
+    }
+
+    Ok(first.clone())
+}
+
+/// Search all indexes, highest priority first
+///
+/// ### Arguments
+/// * `indexes` loaded indexes, highest priority first
+/// * `query` text to look for, everything matches if not given
+/// * `tags` tags the plugins must all have
+pub fn search_indexes<'a>(
+    indexes: &'a [LoadedIndex],
+    query: Option<&str>,
+    tags: &[String],
+) -> Vec<FoundPlugin<'a>> {
+    indexes
+        .iter()
+        .flat_map(|i| {
+            i.index
+                .search(query, tags)
+                .into_iter()
+                .map(|plugin| FoundPlugin {
+                    index_name: &i.source.name,
+                    plugin,
+                })
+        })
+        .collect()
+}
+
+pub fn print_index_plugins(plugins: Vec<FoundPlugin>) {
+    let mut builder = Builder::default();
+    builder.push_record(["Name", "Index", "Version", "Description", "Tags"]);
+
+    // only qualify names that need it
+    let is_ambiguous = |name: &str| {
+        plugins
+            .iter()
+            .filter(|p| p.plugin.name.eq_ignore_ascii_case(name))
+            .count()
+            > 1
+    };
+
+    for found in &plugins {
+        let plugin = found.plugin;
+        builder.push_record([
+            if is_ambiguous(&plugin.name) {
+                found.qualified_name()
+            } else {
+                plugin.name.clone()
+            },
+            found.index_name.to_string(),
+            plugin
+                .latest_version()
+                .map(|v| format!("v{}", v.version.trim_start_matches('v')))
+                .unwrap_or("Unknown".to_string()),
+            plugin.description.clone().unwrap_or("None".to_string()),
+            plugin.tags.join(", "),
+        ]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
+
+pub fn print_index_sources(sources: Vec<IndexSource>) {
+    let mut builder = Builder::default();
+    builder.push_record(["Name", "Priority", "Location", "Plugins", "Updated"]);
+
+    for source in sources {
+        let cached = get_index_cache_file(&source.name)
+            .ok()
+            .and_then(|f| read_cached_index(&f, &source.location));
+
+        builder.push_record([
+            source.name,
+            source.priority.to_string(),
+            source.location,
+            cached
+                .as_ref()
+                .map(|(index, _)| index.plugins.len().to_string())
+                .unwrap_or("Unknown".to_string()),
+            cached
+                .map(|(_, age)| {
+                    format!(
+                        "{} ago",
+                        pretty_duration(&Duration::from_secs(age.as_secs()), None)
+                    )
+                })
+                .unwrap_or("Never".to_string()),
+        ]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plugin_names_are_folder_names() {
+        let index = |name: &str| format!(r#"{{"plugins": [{{"name": "{name}", "source": "x"}}]}}"#);
+
+        assert!(parse_index(&index("plugin"), "index.json").is_ok());
+        for name in ["..", "../plugin", "a/b", ""] {
+            assert_eq!(
+                parse_index(&index(name), "index.json").unwrap_err(),
+                format!("The plugin index has an invalid plugin name '{name}'")
+            );
+        }
+    }
+
+    #[test]
+    fn index_names() {
+        assert!(validate_index_name("community").is_ok());
+        for name in ["", "a/b", "a\\b", "..", "a.b", "a@b"] {
+            assert!(validate_index_name(name).is_err(), "{name}");
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use notify::{RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{common::list_files, data::is_plugin_data_path, progress::print_line};
+
+/// How long to wait for more changes before syncing a mirror
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A plugin folder in `Plugins/` that points to a working copy elsewhere
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Link {
+    /// Path to the working copy
+    pub source: PathBuf,
+    /// Whether `Plugins/` has a copy kept in sync by `cpm link --watch` instead of a symlink
+    pub mirror: bool,
+    pub linked_at: u64,
+}
+
+impl Link {
+    pub fn new(source: &Path, mirror: bool) -> Self {
+        Link {
+            source: source.to_path_buf(),
+            mirror,
+            linked_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        }
+    }
+}
+
+fn link_path(state_path: &Path, folder: &str) -> PathBuf {
+    state_path.join("links").join(format!("{folder}.json"))
+}
+
+/// Read the link of a plugin, if it was linked with `cpm link`
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+/// * `folder` plugin folder name
+pub fn read_link(state_path: &Path, folder: &str) -> Result<Option<Link>, String> {
+    let path = link_path(state_path, folder);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let buf = fs::read_to_string(path).or(Err(format!(
+        "There was an error reading the link of {folder}"
+    )))?;
+    let link = serde_json::from_str(&buf).or(Err(format!(
+        "There was an error parsing the link of {folder}"
+    )))?;
+
+    Ok(Some(link))
+}
+
+/// Record the link of a plugin
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+/// * `folder` plugin folder name
+/// * `link` where the plugin is linked from
+pub fn write_link(state_path: &Path, folder: &str, link: &Link) -> Result<(), String> {
+    let path = link_path(state_path, folder);
+    let err_str = format!("There was an error writing the link of {folder}");
+
+    fs::create_dir_all(path.parent().unwrap()).or(Err(err_str.clone()))?;
+    let buf = serde_json::to_string_pretty(link).or(Err(err_str.clone()))?;
+    fs::write(path, buf).or(Err(err_str))?;
+
+    Ok(())
+}
+
+/// Forget the link of a plugin
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+/// * `folder` plugin folder name
+pub fn remove_link(state_path: &Path, folder: &str) -> Result<(), String> {
+    let path = link_path(state_path, folder);
+    if path.is_file() {
+        fs::remove_file(path).or(Err(format!(
+            "There was an error removing the link of {folder}"
+        )))?;
+    }
+
+    Ok(())
+}
+
+/// Get all linked plugins by folder name
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+pub fn list_links(state_path: &Path) -> BTreeMap<String, Link> {
+    let Ok(entries) = fs::read_dir(state_path.join("links")) else {
+        return BTreeMap::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let folder = name.strip_suffix(".json")?;
+            let link = read_link(state_path, folder).ok()??;
+            Some((folder.to_string(), link))
+        })
+        .collect()
+}
+
+/// Whether a path is a symlink, without following it
+pub fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// Remove a symlink to a folder, even if the folder is gone
+pub fn remove_symlink(link: &Path) -> std::io::Result<()> {
+    // directory symlinks are removed like folders on Windows
+    fs::remove_file(link).or_else(|_| fs::remove_dir(link))
+}
+
+/// Create a symlink to a folder
+///
+/// ### Arguments
+/// * `source` folder to link to
+/// * `link` path of the symlink
+pub fn create_symlink(source: &Path, link: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(source, link);
+    #[cfg(windows)]
+    let result = std::os::windows::fs::symlink_dir(source, link);
+
+    result.map_err(|e| {
+        format!(
+            "Could not link {}: {e}, use --watch to mirror it instead",
+            link.to_string_lossy()
+        )
+    })
+}
+
+/// Whether a file of a working copy is left out of its mirror
+fn is_ignored_path(path: &str) -> bool {
+    is_plugin_data_path(path) || path == ".git" || path.starts_with(".git/")
+}
+
+/// List all folders in a folder as sorted `/` separated paths relative to it
+fn list_folders(path: &Path) -> Vec<String> {
+    let mut folders = Vec::new();
+    let Ok(entries) = fs::read_dir(path) else {
+        return folders;
+    };
+
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            folders.extend(
+                list_folders(&entry.path())
+                    .into_iter()
+                    .map(|subpath| format!("{name}/{subpath}")),
+            );
+            folders.push(name);
+        }
+    }
+
+    folders.sort();
+    folders
+}
+
+/// Make a mirror match its working copy, returning how many files changed
+///
+/// The mirror's data folder is never touched. Folders left empty by removed files are removed
+/// too, unless the working copy still has them.
+///
+/// ### Arguments
+/// * `source` path to the working copy
+/// * `mirror` path to the mirror in `Plugins/`
+pub fn sync_mirror(source: &Path, mirror: &Path) -> Result<usize, String> {
+    let err_str = format!("There was an error syncing {}", mirror.to_string_lossy());
+    let mut changed = 0;
+
+    let source_files: Vec<String> = list_files(source)
+        .into_iter()
+        .map(|(file, _)| file)
+        .filter(|file| !is_ignored_path(file))
+        .collect();
+
+    for file in &source_files {
+        let contents = fs::read(source.join(file)).or(Err(err_str.clone()))?;
+        let target = mirror.join(file);
+        if fs::read(&target).is_ok_and(|current| current == contents) {
+            continue;
+        }
+
+        fs::create_dir_all(target.parent().unwrap()).or(Err(err_str.clone()))?;
+        fs::write(&target, contents).or(Err(err_str.clone()))?;
+        changed += 1;
+    }
+
+    for (file, _) in list_files(mirror) {
+        if is_plugin_data_path(&file) || source_files.contains(&file) {
+            continue;
+        }
+
+        fs::remove_file(mirror.join(&file)).or(Err(err_str.clone()))?;
+        changed += 1;
+    }
+
+    // sorted paths have subfolders after their parent, so going backwards removes them first
+    for folder in list_folders(mirror).iter().rev() {
+        let path = mirror.join(folder);
+        if is_plugin_data_path(folder)
+            || source.join(folder).is_dir()
+            || fs::read_dir(&path).map_or(true, |mut e| e.next().is_some())
+        {
+            continue;
+        }
+
+        fs::remove_dir(&path).or(Err(err_str.clone()))?;
+    }
+
+    Ok(changed)
+}
+
+/// Keep a mirror in sync with its working copy until cpm is stopped
+///
+/// ### Arguments
+/// * `source` path to the working copy
+/// * `mirror` path to the mirror in `Plugins/`
+pub fn watch_mirror(source: &Path, mirror: &Path) -> Result<(), String> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).or(Err("There was an error starting the file watcher"))?;
+    watcher
+        .watch(source, RecursiveMode::Recursive)
+        .or(Err(format!("Could not watch {}", source.to_string_lossy())))?;
+
+    print_line(&format!(
+        "Watching {} for changes, press Ctrl+C to stop",
+        source.to_string_lossy()
+    ));
+
+    while rx.recv().is_ok() {
+        // wait for a burst of changes, e.g. an editor saving several files, to end
+        while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+
+        match sync_mirror(source, mirror) {
+            Ok(0) => {}
+            Ok(changed) => print_line(&format!("Synced {changed} file(s)")),
+            Err(message) => print_line(&format!("Error: {message}")),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_removes_empty_folders() {
+        let root = std::env::temp_dir().join(format!("cpm-link-{}", std::process::id()));
+        let (source, mirror) = (root.join("source"), root.join("mirror"));
+        for folder in ["lib/util", "empty", "gone/deep"] {
+            fs::create_dir_all(source.join(folder)).unwrap();
+        }
+        fs::write(source.join("init.lua"), "").unwrap();
+        fs::write(source.join("lib/util/a.lua"), "").unwrap();
+        fs::write(source.join("gone/deep/b.lua"), "").unwrap();
+        fs::create_dir_all(mirror.join("data/cache")).unwrap();
+
+        assert_eq!(sync_mirror(&source, &mirror), Ok(3));
+
+        fs::remove_dir_all(source.join("gone")).unwrap();
+        fs::remove_file(source.join("lib/util/a.lua")).unwrap();
+        fs::create_dir_all(mirror.join("empty")).unwrap();
+        assert_eq!(sync_mirror(&source, &mirror), Ok(2));
+
+        let folders = list_folders(&mirror);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(folders, ["data", "data/cache", "empty", "lib", "lib/util"]);
+    }
+}
@@ -0,0 +1,863 @@
+use mlua::{ChunkMode, Lua};
+use std::{fmt, fs, path::Path};
+
+use super::{common::list_files, data::is_plugin_data_path};
+
+const KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Modules Lua has already loaded, which `require` returns without looking for files
+const BUILTIN_MODULES: [&str; 10] = [
+    "_G",
+    "coroutine",
+    "debug",
+    "io",
+    "math",
+    "os",
+    "package",
+    "string",
+    "table",
+    "utf8",
+];
+
+/// Symbols, longest first so they are matched greedily
+const SYMBOLS: [&str; 33] = [
+    "...", "..", "==", "~=", "<=", ">=", "<<", ">>", "//", "::", "+", "-", "*", "/", "%", "^", "#",
+    "&", "~", "|", "<", ">", "=", "(", ")", "{", "}", "[", "]", ";", ":", ",", ".",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Name(String),
+    Keyword(&'static str),
+    /// A string literal with its escapes resolved
+    Str(String),
+    Number(String),
+    Symbol(&'static str),
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Name(text) | Token::Number(text) => write!(f, "'{text}'"),
+            Token::Keyword(text) | Token::Symbol(text) => write!(f, "'{text}'"),
+            Token::Str(text) => write!(f, "'\"{text}\"'"),
+            Token::Eof => write!(f, "<eof>"),
+        }
+    }
+}
+
+/// A token and the line it starts on
+#[derive(Debug, Clone)]
+pub struct Spanned {
+    pub token: Token,
+    pub line: usize,
+}
+
+/// A syntax error in Lua source, with the position it was found at
+#[derive(Debug, Clone)]
+pub struct LuaError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LuaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Lexer {
+    fn new(source: &str) -> Self {
+        Lexer {
+            chars: source.chars().collect(),
+            pos: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += 1;
+
+        // \r\n and \n\r count as one line break, like Lua does
+        if c == '\n' || c == '\r' {
+            if let Some(other) = self.peek(0) {
+                if (other == '\n' || other == '\r') && other != c {
+                    self.pos += 1;
+                }
+            }
+            self.line += 1;
+            self.column = 1;
+            return Some('\n');
+        }
+
+        self.column += 1;
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> LuaError {
+        LuaError {
+            line: self.line,
+            column: self.column,
+            message: message.to_string(),
+        }
+    }
+
+    /// Count the `=` of a long bracket after its first `[` or `]`, `None` if it isn't one
+    fn long_bracket_level(&self, bracket: char) -> Option<usize> {
+        let mut level = 0;
+        while self.peek(1 + level) == Some('=') {
+            level += 1;
+        }
+
+        (self.peek(1 + level) == Some(bracket)).then_some(level)
+    }
+
+    fn read_long_string(&mut self, level: usize, what: &str) -> Result<String, LuaError> {
+        for _ in 0..level + 2 {
+            self.next();
+        }
+        // a line break right after the opening bracket is skipped
+        if matches!(self.peek(0), Some('\n' | '\r')) {
+            self.next();
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.peek(0) {
+                None => return Err(self.error(&format!("unfinished long {what}"))),
+                Some(']') if self.long_bracket_level(']') == Some(level) => {
+                    for _ in 0..level + 2 {
+                        self.next();
+                    }
+                    return Ok(value);
+                }
+                Some(_) => value.push(self.next().unwrap()),
+            }
+        }
+    }
+
+    fn read_string(&mut self, quote: char) -> Result<String, LuaError> {
+        self.next();
+        let mut value = String::new();
+
+        loop {
+            let Some(c) = self.peek(0) else {
+                return Err(self.error("unfinished string"));
+            };
+
+            match c {
+                '\n' | '\r' => return Err(self.error("unfinished string")),
+                '\\' => {
+                    self.next();
+                    self.read_escape(&mut value)?;
+                }
+                c if c == quote => {
+                    self.next();
+                    return Ok(value);
+                }
+                _ => value.push(self.next().unwrap()),
+            }
+        }
+    }
+
+    fn read_escape(&mut self, value: &mut String) -> Result<(), LuaError> {
+        let Some(c) = self.peek(0) else {
+            return Err(self.error("unfinished string"));
+        };
+
+        let escaped = match c {
+            'a' => '\x07',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'v' => '\x0b',
+            '\\' | '"' | '\'' => c,
+            '\n' | '\r' => '\n',
+            'x' => {
+                self.next();
+                let mut code = 0;
+                for _ in 0..2 {
+                    let digit = self
+                        .peek(0)
+                        .and_then(|d| d.to_digit(16))
+                        .ok_or(self.error("hexadecimal digit expected"))?;
+                    code = code * 16 + digit;
+                    self.next();
+                }
+                value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                return Ok(());
+            }
+            'z' => {
+                self.next();
+                while self.peek(0).is_some_and(|c| c.is_ascii_whitespace()) {
+                    self.next();
+                }
+                return Ok(());
+            }
+            'u' => {
+                self.next();
+                if self.peek(0) != Some('{') {
+                    return Err(self.error("missing '{' in \\u{xxxx}"));
+                }
+                self.next();
+
+                let mut code: u64 = 0;
+                let mut digits = 0;
+                while let Some(digit) = self.peek(0).and_then(|d| d.to_digit(16)) {
+                    code = code * 16 + digit as u64;
+                    digits += 1;
+                    if code > 0x7FFFFFFF {
+                        return Err(self.error("UTF-8 value too large"));
+                    }
+                    self.next();
+                }
+                if digits == 0 {
+                    return Err(self.error("hexadecimal digit expected"));
+                }
+                if self.peek(0) != Some('}') {
+                    return Err(self.error("missing '}' in \\u{xxxx}"));
+                }
+                self.next();
+
+                value.push(char::from_u32(code as u32).unwrap_or(char::REPLACEMENT_CHARACTER));
+                return Ok(());
+            }
+            c if c.is_ascii_digit() => {
+                let mut code = 0;
+                for _ in 0..3 {
+                    match self.peek(0).and_then(|d| d.to_digit(10)) {
+                        Some(digit) => {
+                            code = code * 10 + digit;
+                            self.next();
+                        }
+                        None => break,
+                    }
+                }
+                if code > 255 {
+                    return Err(self.error("decimal escape too large"));
+                }
+                value.push(char::from_u32(code).unwrap());
+                return Ok(());
+            }
+            _ => return Err(self.error("invalid escape sequence")),
+        };
+
+        self.next();
+        value.push(escaped);
+        Ok(())
+    }
+
+    fn read_number(&mut self) -> Result<String, LuaError> {
+        let is_hex = self.peek(0) == Some('0') && matches!(self.peek(1), Some('x' | 'X'));
+        let exponent = if is_hex { ['p', 'P'] } else { ['e', 'E'] };
+        let mut text = String::new();
+        if is_hex {
+            text.push(self.next().unwrap());
+            text.push(self.next().unwrap());
+        }
+
+        // read greedily like Lua does, then check the whole thing
+        loop {
+            match self.peek(0) {
+                Some(c) if exponent.contains(&c) => {
+                    text.push(self.next().unwrap());
+                    if matches!(self.peek(0), Some('+' | '-')) {
+                        text.push(self.next().unwrap());
+                    }
+                }
+                Some(c) if c.is_ascii_hexdigit() || c == '.' => text.push(self.next().unwrap()),
+                _ => break,
+            }
+        }
+        if self
+            .peek(0)
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            text.push(self.next().unwrap());
+        }
+
+        if is_valid_number(&text) {
+            Ok(text)
+        } else {
+            Err(self.error(&format!("malformed number near '{text}'")))
+        }
+    }
+
+    fn skip_comment(&mut self) -> Result<(), LuaError> {
+        self.next();
+        self.next();
+
+        if self.peek(0) == Some('[') {
+            if let Some(level) = self.long_bracket_level('[') {
+                self.read_long_string(level, "comment")?;
+                return Ok(());
+            }
+        }
+
+        while !matches!(self.peek(0), None | Some('\n' | '\r')) {
+            self.next();
+        }
+        Ok(())
+    }
+
+    fn next_token(&mut self) -> Result<Spanned, LuaError> {
+        loop {
+            match self.peek(0) {
+                Some(c) if c.is_ascii_whitespace() || c == '\x0b' => {
+                    self.next();
+                }
+                Some('-') if self.peek(1) == Some('-') => self.skip_comment()?,
+                _ => break,
+            }
+        }
+
+        let line = self.line;
+        let spanned = |token| Spanned { token, line };
+
+        let Some(c) = self.peek(0) else {
+            return Ok(spanned(Token::Eof));
+        };
+
+        if c.is_ascii_alphabetic() || c == '_' {
+            let mut name = String::new();
+            while let Some(c) = self
+                .peek(0)
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+            {
+                name.push(c);
+                self.next();
+            }
+
+            return Ok(spanned(match KEYWORDS.iter().find(|k| **k == name) {
+                Some(keyword) => Token::Keyword(keyword),
+                None => Token::Name(name),
+            }));
+        }
+
+        if c.is_ascii_digit() || (c == '.' && self.peek(1).is_some_and(|d| d.is_ascii_digit())) {
+            return Ok(spanned(Token::Number(self.read_number()?)));
+        }
+
+        if c == '"' || c == '\'' {
+            return Ok(spanned(Token::Str(self.read_string(c)?)));
+        }
+
+        if c == '[' {
+            if let Some(level) = self.long_bracket_level('[') {
+                return Ok(spanned(Token::Str(self.read_long_string(level, "string")?)));
+            }
+            if self.peek(1) == Some('=') {
+                return Err(self.error("invalid long string delimiter"));
+            }
+        }
+
+        for symbol in SYMBOLS {
+            if symbol
+                .chars()
+                .enumerate()
+                .all(|(i, s)| self.peek(i) == Some(s))
+            {
+                for _ in 0..symbol.len() {
+                    self.next();
+                }
+                return Ok(spanned(Token::Symbol(symbol)));
+            }
+        }
+
+        Err(self.error(&format!("unexpected symbol near '{c}'")))
+    }
+}
+
+fn is_valid_number(text: &str) -> bool {
+    let (digits, rest, exponent) = match text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        Some(rest) => (16, rest, ['p', 'P']),
+        None => (10, text, ['e', 'E']),
+    };
+
+    let (mantissa, exponent) = match rest.split_once(exponent) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (rest, None),
+    };
+
+    let mut parts = mantissa.splitn(2, '.');
+    let whole = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("");
+    let is_digits = |s: &str| s.chars().all(|c| c.is_digit(digits));
+
+    let valid_mantissa =
+        !(whole.is_empty() && fraction.is_empty()) && is_digits(whole) && is_digits(fraction);
+    let valid_exponent = exponent.is_none_or(|e| {
+        let e = e.strip_prefix(['+', '-']).unwrap_or(e);
+        !e.is_empty() && e.chars().all(|c| c.is_ascii_digit())
+    });
+
+    valid_mantissa && valid_exponent
+}
+
+/// Split Lua source into tokens, ending with [`Token::Eof`]
+pub fn tokenize(source: &str) -> Result<Vec<Spanned>, LuaError> {
+    let mut lexer = Lexer::new(source.strip_prefix('\u{feff}').unwrap_or(source));
+
+    // a first line starting with # is skipped, e.g. a shebang
+    if lexer.peek(0) == Some('#') {
+        while !matches!(lexer.peek(0), None | Some('\n' | '\r')) {
+            lexer.next();
+        }
+    }
+
+    let mut tokens = Vec::new();
+    loop {
+        let spanned = lexer.next_token()?;
+        let is_eof = spanned.token == Token::Eof;
+        tokens.push(spanned);
+
+        if is_eof {
+            return Ok(tokens);
+        }
+    }
+}
+
+/// Check Lua 5.4 source for the errors Lua finds when compiling it
+///
+/// The source is compiled with the Lua Chatterino embeds, without running it. Returns the error
+/// as `name:line: message`.
+///
+/// ### Arguments
+/// * `source` contents of the file
+/// * `name` name of the file in error messages
+pub fn check_syntax(source: &[u8], name: &str) -> Result<(), String> {
+    // like loading a file, skip a BOM and a first line starting with `#`, but keep line numbers
+    let source = source.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(source);
+    let source = match source.first() {
+        Some(b'#') => {
+            &source[source
+                .iter()
+                .position(|&b| b == b'\n')
+                .unwrap_or(source.len())..]
+        }
+        _ => source,
+    };
+
+    Lua::new()
+        .load(source)
+        .set_name(format!("@{name}"))
+        .set_mode(ChunkMode::Text)
+        .into_function()
+        .map(|_| ())
+        .map_err(|e| match e {
+            mlua::Error::SyntaxError { message, .. } => message,
+            e => format!("{name}: {e}"),
+        })
+}
+
+/// Get the literal string arguments of a call, `None` for arguments that are anything else
+///
+/// Handles both `f("a", b)` and `f "a"`.
+///
+/// ### Arguments
+/// * `tokens` tokens of the whole file
+/// * `start` index of the token right after the function being called
+pub fn call_string_arguments(tokens: &[Spanned], start: usize) -> Vec<Option<String>> {
+    match tokens.get(start).map(|t| &t.token) {
+        Some(Token::Str(value)) => return vec![Some(value.clone())],
+        Some(Token::Symbol("(")) => {}
+        _ => return Vec::new(),
+    }
+
+    let mut arguments = Vec::new();
+    let mut argument = Vec::new();
+    let mut depth = 0;
+    for spanned in &tokens[start + 1..] {
+        match &spanned.token {
+            Token::Symbol("(" | "{" | "[") => depth += 1,
+            Token::Symbol(")") if depth == 0 => break,
+            Token::Symbol(")" | "}" | "]") => depth -= 1,
+            Token::Symbol(",") if depth == 0 => {
+                arguments.push(std::mem::take(&mut argument));
+                continue;
+            }
+            Token::Eof => break,
+            _ => {}
+        }
+        argument.push(&spanned.token);
+    }
+    if !argument.is_empty() {
+        arguments.push(argument);
+    }
+
+    arguments
+        .into_iter()
+        .map(|argument| match argument.as_slice() {
+            [Token::Str(value)] => Some(value.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether a name at a token is a variable of its own rather than a field or a definition
+pub fn is_global_name(tokens: &[Spanned], i: usize) -> bool {
+    i == 0
+        || !matches!(
+            tokens[i - 1].token,
+            Token::Symbol("." | ":") | Token::Keyword("local" | "function")
+        )
+}
+
+/// Get the modules required with a literal name, with the line of each `require`
+pub fn find_requires(tokens: &[Spanned]) -> Vec<(usize, String)> {
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, spanned)| {
+            matches!(&spanned.token, Token::Name(name) if name == "require")
+                && is_global_name(tokens, *i)
+        })
+        .filter_map(|(i, spanned)| {
+            let module = call_string_arguments(tokens, i + 1)
+                .into_iter()
+                .next()
+                .flatten()?;
+            Some((spanned.line, module))
+        })
+        .collect()
+}
+
+/// Get the files `require` would look for a module in, like Chatterino's loader does
+///
+/// Modules are only ever loaded from inside the plugin folder.
+pub fn module_paths(module: &str) -> Vec<String> {
+    let path = module.replace('.', "/");
+    vec![format!("{path}.lua"), format!("{path}/init.lua")]
+}
+
+/// Get the folders of git submodules from a plugin's `.gitmodules`
+fn read_submodule_paths(plugin_path: &Path) -> Vec<String> {
+    let Ok(gitmodules) = fs::read_to_string(plugin_path.join(".gitmodules")) else {
+        return Vec::new();
+    };
+
+    gitmodules
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "path").then(|| value.trim().trim_end_matches('/').to_string())
+        })
+        .collect()
+}
+
+/// Check that every module required by a plugin's Lua files is in the plugin folder
+///
+/// Returns the unresolved modules as `file:line: message`, files with syntax errors are left to
+/// [`check_lua_files`].
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder
+pub fn check_requires(plugin_path: &Path) -> Vec<String> {
+    let submodules = read_submodule_paths(plugin_path);
+    let mut errors = Vec::new();
+
+    for file in list_lua_files(plugin_path) {
+        let Ok(buf) = fs::read(plugin_path.join(&file)) else {
+            continue;
+        };
+        let Ok(tokens) = tokenize(&String::from_utf8_lossy(&buf)) else {
+            continue;
+        };
+
+        for (line, module) in find_requires(&tokens) {
+            if BUILTIN_MODULES.contains(&module.as_str()) {
+                continue;
+            }
+
+            let paths = module_paths(&module);
+            if paths.iter().any(|p| plugin_path.join(p).is_file()) {
+                continue;
+            }
+
+            let mut error = format!(
+                "{file}:{line}: module '{module}' not found, looked for {}",
+                paths.join(" and ")
+            );
+            if let Some(submodule) = submodules
+                .iter()
+                .find(|s| paths[0].starts_with(&format!("{s}/")))
+            {
+                error.push_str(&format!(
+                    " ({submodule} is a git submodule, which GitHub archives leave out)"
+                ));
+            }
+            errors.push(error);
+        }
+    }
+
+    errors
+}
+
+/// Get the `/` separated paths of all Lua files in a plugin folder, except its data folder
+pub fn list_lua_files(plugin_path: &Path) -> Vec<String> {
+    list_files(plugin_path)
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| path.ends_with(".lua") && !is_plugin_data_path(path))
+        .collect()
+}
+
+/// Check every Lua file in a plugin folder for syntax errors
+///
+/// Returns the errors as `file:line: message`.
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder
+pub fn check_lua_files(plugin_path: &Path) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    for path in list_lua_files(plugin_path) {
+        let Ok(source) = fs::read(plugin_path.join(&path)) else {
+            errors.push(format!("{path}: could not be read"));
+            continue;
+        };
+
+        if let Err(error) = check_syntax(&source, &path) {
+            errors.push(error);
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(source: &str) -> Vec<Token> {
+        tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.token)
+            .collect()
+    }
+
+    fn string_value(source: &str) -> String {
+        match tokens(source).as_slice() {
+            [Token::Str(value), Token::Eof] => value.clone(),
+            other => panic!("expected a single string, got {other:?}"),
+        }
+    }
+
+    fn syntax_error(source: &str) -> String {
+        check_syntax(source.as_bytes(), "init.lua").expect_err(source)
+    }
+
+    #[test]
+    fn valid_numerals() {
+        for numeral in [
+            "3",
+            "345",
+            "0xff",
+            "0xBEBADA",
+            "3.0",
+            "3.1416",
+            "314.16e-2",
+            "0.31416E1",
+            "34e1",
+            "0x0.1E",
+            "0xA23p-4",
+            "0X1.921FB54442D18P+1",
+            ".5",
+            "5.",
+        ] {
+            assert_eq!(
+                tokens(numeral),
+                [Token::Number(numeral.to_string()), Token::Eof]
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_numerals() {
+        for numeral in ["3x", "0x", "1e", "1e+", "1.2.3", "0xp1", "12abc"] {
+            let error = tokenize(numeral).expect_err(numeral);
+            assert!(
+                error.message.starts_with("malformed number"),
+                "{numeral}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(string_value(r#""a\tb\nc""#), "a\tb\nc");
+        assert_eq!(string_value(r#"'\'\"\\'"#), "'\"\\");
+        assert_eq!(string_value(r#""\x41\65\u{48}\u{20AC}""#), "AAH\u{20AC}");
+        assert_eq!(string_value("\"a\\z   \n  b\""), "ab");
+        assert_eq!(string_value("\"a\\\nb\""), "a\nb");
+        assert_eq!(string_value(r#""\0659""#), "A9");
+    }
+
+    #[test]
+    fn invalid_string_escapes() {
+        for (source, message) in [
+            (r#""\q""#, "invalid escape sequence"),
+            (r#""\xg0""#, "hexadecimal digit expected"),
+            (r#""\256""#, "decimal escape too large"),
+            (r#""\u48""#, "missing '{' in \\u{xxxx}"),
+            (r#""\u{48""#, "missing '}' in \\u{xxxx}"),
+            (r#""\u{80000000}""#, "UTF-8 value too large"),
+            ("\"abc\ndef\"", "unfinished string"),
+            ("'abc", "unfinished string"),
+        ] {
+            assert_eq!(tokenize(source).expect_err(source).message, message);
+        }
+    }
+
+    #[test]
+    fn nested_long_brackets() {
+        assert_eq!(string_value("[[a]]"), "a");
+        assert_eq!(string_value("[==[a]]b]=]c]==]"), "a]]b]=]c");
+        assert_eq!(string_value("[=[\nfirst line]]\n]=]"), "first line]]\n");
+        assert_eq!(
+            tokens("--[==[ a ]] comment ]=] ]==] x"),
+            [Token::Name("x".to_string()), Token::Eof]
+        );
+
+        assert_eq!(
+            tokenize("[==[a]=]").unwrap_err().message,
+            "unfinished long string"
+        );
+        assert_eq!(
+            tokenize("--[[a").unwrap_err().message,
+            "unfinished long comment"
+        );
+        assert_eq!(
+            tokenize("[=a").unwrap_err().message,
+            "invalid long string delimiter"
+        );
+    }
+
+    #[test]
+    fn operator_precedence() {
+        for source in [
+            "x = 1 + 2 * 3 ^ -4 ^ 5",
+            "x = not a == b and c or d",
+            "x = a .. b .. c == 'abc' and #t > 0",
+            "x = 1 << 2 | 3 & 4 ~ ~5 // 6 % 7",
+            "x = -2 ^ 2 < 3 <= 4 ~= 5",
+            "x = (a or b)(c)[d]:e 'f' { g }",
+        ] {
+            assert!(
+                check_syntax(source.as_bytes(), "init.lua").is_ok(),
+                "{source}"
+            );
+        }
+
+        for source in ["x = 1 + * 2", "x = a and or b", "x = not", "x = 1 .. "] {
+            assert!(
+                check_syntax(source.as_bytes(), "init.lua").is_err(),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn valid_statements() {
+        let source = r#"#!/usr/bin/env lua
+local a <const>, b <close> = 1, nil
+local function f(x, ...)
+    for i = 1, 10, 2 do
+        if i > x then break elseif i == 3 then goto continue else end
+        ::continue::
+    end
+    for k, v in pairs({...}) do print(k, v) end
+    while false do repeat local y = 1 until y end
+    return select('#', ...)
+end
+c2.register_command("/hello", function(ctx) ctx.channel:add_system_message("hi") end)
+"#;
+        if let Err(error) = check_syntax(source.as_bytes(), "init.lua") {
+            panic!("{error}");
+        }
+    }
+
+    #[test]
+    fn error_positions() {
+        for (source, error) in [
+            (
+                "local x = 1\nx = = 2",
+                "init.lua:2: unexpected symbol near '='",
+            ),
+            (
+                "if x then\n  y()\n",
+                "init.lua:3: 'end' expected (to close 'if' at line 1) near <eof>",
+            ),
+            (
+                "local t = {1, 2\nprint(t)",
+                "init.lua:2: '}' expected (to close '{' at line 1) near 'print'",
+            ),
+            (
+                "function f()\n  return ...\nend",
+                "init.lua:2: cannot use '...' outside a vararg function near '...'",
+            ),
+            ("x = 1\nbreak", "init.lua:2: break outside loop at line 2"),
+            (
+                "x = 'a\\q'",
+                "init.lua:1: invalid escape sequence near ''a\\q'",
+            ),
+            (
+                "#!/usr/bin/env lua\n\n  x = 0x",
+                "init.lua:3: malformed number near '0x'",
+            ),
+        ] {
+            assert_eq!(syntax_error(source), error);
+        }
+    }
+
+    #[test]
+    fn compile_errors() {
+        for (source, error) in [
+            (
+                "local x <const> = 1; x = 2",
+                "init.lua:1: attempt to assign to const variable 'x'",
+            ),
+            (
+                "goto nowhere",
+                "init.lua:1: no visible label 'nowhere' for <goto> at line 1",
+            ),
+            (
+                "do ::a:: end\ngoto a",
+                "init.lua:2: no visible label 'a' for <goto> at line 2",
+            ),
+            (
+                "goto skip\nlocal y = 1\n::skip::\nprint(y)",
+                "init.lua:4: <goto skip> at line 1 jumps into the scope of local 'y'",
+            ),
+            (
+                "local x <unknown> = 1",
+                "init.lua:1: unknown attribute 'unknown'",
+            ),
+        ] {
+            assert_eq!(syntax_error(source), error);
+        }
+
+        assert!(syntax_error("\x1bLua").contains("attempt to load a binary chunk"));
+    }
+}
@@ -1,3 +1,25 @@
+pub mod adopt;
+pub mod audit;
+pub mod batch;
+pub mod cache;
 pub mod common;
+pub mod config;
+pub mod conflicts;
+pub mod data;
+pub mod doctor;
+pub mod filter;
 pub mod github;
+pub mod ignore;
+pub mod index;
+pub mod link;
+pub mod lua;
+pub mod pack;
+pub mod plan;
 pub mod plugin;
+pub mod progress;
+pub mod provenance;
+pub mod runtime;
+pub mod scaffold;
+pub mod settings;
+pub mod trust;
+pub mod verify;
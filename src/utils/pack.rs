@@ -0,0 +1,267 @@
+use flate2::{Compression, GzBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+use tar::{Builder, Header};
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipWriter};
+
+use super::{common::list_files, data::is_plugin_data_path, filter::FileFilter, verify::hash_file};
+
+/// File at the root of a packed plugin describing what is in it
+pub const MANIFEST_FILE: &str = "cpm-manifest.json";
+
+/// Modification time of every packed file, so packing the same files gives the same archive
+const PACK_MTIME: u64 = 0;
+
+/// What `cpm pack` put into an archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackManifest {
+    pub name: String,
+    pub folder: String,
+    pub version: String,
+    /// sha256 of every packed file
+    pub files: BTreeMap<String, String>,
+}
+
+/// Get the files of a plugin that go into a pack, sorted
+///
+/// The data folder, `.git` and anything the filter leaves out, like files matched by `.cpmignore`,
+/// are left out.
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder
+/// * `filter` which files to keep
+/// * `skip` a folder inside the plugin to leave out, e.g. where packs are written to
+pub fn list_pack_files(plugin_path: &Path, filter: &FileFilter, skip: Option<&str>) -> Vec<String> {
+    list_files(plugin_path)
+        .into_iter()
+        .map(|(file, _)| file)
+        .filter(|file| {
+            !is_plugin_data_path(file)
+                && file != ".git"
+                && !file.starts_with(".git/")
+                && file != MANIFEST_FILE
+                && !skip.is_some_and(|skip| file.starts_with(&format!("{skip}/")))
+                && filter.is_kept(file)
+        })
+        .collect()
+}
+
+/// Create the manifest of a pack
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder
+/// * `files` files that go into the pack
+/// * `name` plugin name from `info.json`
+/// * `folder` plugin folder name
+/// * `version` plugin version from `info.json`
+pub fn create_manifest(
+    plugin_path: &Path,
+    files: &[String],
+    name: &str,
+    folder: &str,
+    version: &str,
+) -> Result<PackManifest, String> {
+    let mut hashes = BTreeMap::new();
+    for file in files {
+        hashes.insert(file.clone(), hash_file(&plugin_path.join(file))?);
+    }
+
+    Ok(PackManifest {
+        name: name.to_string(),
+        folder: folder.to_string(),
+        version: version.to_string(),
+        files: hashes,
+    })
+}
+
+/// Get the files of a pack with their contents, manifest first
+fn read_pack_entries(
+    plugin_path: &Path,
+    manifest: &PackManifest,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let manifest_json = serde_json::to_string_pretty(manifest)
+        .or(Err("There was an error creating the manifest"))?;
+    let mut entries = vec![(MANIFEST_FILE.to_string(), manifest_json.into_bytes())];
+
+    for file in manifest.files.keys() {
+        let contents = fs::read(plugin_path.join(file))
+            .or(Err(format!("There was an error reading {file}")))?;
+        entries.push((file.clone(), contents));
+    }
+
+    Ok(entries)
+}
+
+/// Write a plugin into a .tar.gz with every file in a top-level folder, like GitHub's tarballs
+///
+/// Entries are sorted and their owners, permissions and times fixed, so the same files always
+/// give the same archive.
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder
+/// * `manifest` manifest of the pack, listing the files to write
+/// * `archive_path` path of the archive to create
+pub fn write_tar_gz(
+    plugin_path: &Path,
+    manifest: &PackManifest,
+    archive_path: &Path,
+) -> Result<(), String> {
+    let err_str = format!(
+        "There was an error writing {}",
+        archive_path.to_string_lossy()
+    );
+
+    let file = File::create(archive_path).or(Err(err_str.clone()))?;
+    let encoder = GzBuilder::new()
+        .mtime(PACK_MTIME as u32)
+        .write(file, Compression::best());
+    let mut builder = Builder::new(encoder);
+
+    for (path, contents) in read_pack_entries(plugin_path, manifest)? {
+        let mut header = Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(PACK_MTIME);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_entry_type(tar::EntryType::Regular);
+
+        builder
+            .append_data(
+                &mut header,
+                format!("{}/{path}", manifest.folder),
+                contents.as_slice(),
+            )
+            .or(Err(err_str.clone()))?;
+    }
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .and_then(|mut file| file.flush())
+        .or(Err(err_str))?;
+
+    Ok(())
+}
+
+/// Write a plugin into a .zip with every file in a top-level folder
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder
+/// * `manifest` manifest of the pack, listing the files to write
+/// * `archive_path` path of the archive to create
+pub fn write_zip(
+    plugin_path: &Path,
+    manifest: &PackManifest,
+    archive_path: &Path,
+) -> Result<(), String> {
+    let err_str = format!(
+        "There was an error writing {}",
+        archive_path.to_string_lossy()
+    );
+
+    let file = File::create(archive_path).or(Err(err_str.clone()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(DateTime::default())
+        .unix_permissions(0o644);
+
+    for (path, contents) in read_pack_entries(plugin_path, manifest)? {
+        zip.start_file(format!("{}/{path}", manifest.folder), options)
+            .or(Err(err_str.clone()))?;
+        zip.write_all(&contents).or(Err(err_str.clone()))?;
+    }
+
+    zip.finish().or(Err(err_str))?;
+
+    Ok(())
+}
+
+/// Write a `sha256sum` compatible `<archive>.sha256` next to an archive, returning the hash
+///
+/// ### Arguments
+/// * `archive_path` path of the archive
+pub fn write_sha256_sidecar(archive_path: &Path) -> Result<String, String> {
+    let hash = hash_file(archive_path)?;
+    let file_name = archive_path.file_name().unwrap().to_string_lossy();
+    let sidecar_path = archive_path.with_file_name(format!("{file_name}.sha256"));
+
+    fs::write(&sidecar_path, format!("{hash}  {file_name}\n")).or(Err(format!(
+        "There was an error writing {}",
+        sidecar_path.to_string_lossy()
+    )))?;
+
+    Ok(hash)
+}
+
+/// Check the sha256 of a download against a `sha256sum` style sidecar
+///
+/// ### Arguments
+/// * `actual` sha256 of the downloaded archive, in lowercase hex
+/// * `sidecar` contents of the `.sha256` file
+/// * `name` name of the archive, for error messages
+pub fn check_sha256_sidecar(actual: &str, sidecar: &str, name: &str) -> Result<(), String> {
+    let expected = sidecar
+        .split_whitespace()
+        .next()
+        .ok_or(format!("{name}.sha256 is empty"))?
+        .to_lowercase();
+
+    if actual != expected {
+        return Err(format!(
+            "{name} does not match its sha256, expected {expected} but got {actual}"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check extracted files against the manifest of a pack, then remove the manifest
+///
+/// ### Arguments
+/// * `plugin_path` folder the pack was extracted into
+/// * `filter` filter the pack was extracted with, files it leaves out aren't expected
+pub fn check_pack_manifest(
+    plugin_path: &Path,
+    filter: &FileFilter,
+) -> Result<PackManifest, String> {
+    let manifest_path = plugin_path.join(MANIFEST_FILE);
+    let buf = fs::read_to_string(&manifest_path)
+        .or(Err(format!("The release archive has no {MANIFEST_FILE}")))?;
+    let manifest: PackManifest = serde_json::from_str(&buf)
+        .or(Err(format!("There was an error parsing {MANIFEST_FILE}")))?;
+    fs::remove_file(&manifest_path)
+        .or(Err(format!("There was an error removing {MANIFEST_FILE}")))?;
+
+    let files: Vec<String> = list_files(plugin_path)
+        .into_iter()
+        .map(|(file, _)| file)
+        .collect();
+    for file in &files {
+        let Some(expected) = manifest.files.get(file) else {
+            return Err(format!(
+                "{file} is in the release archive but not in its manifest"
+            ));
+        };
+        if &hash_file(&plugin_path.join(file))? != expected {
+            return Err(format!("{file} does not match the hash in the manifest"));
+        }
+    }
+    if let Some(missing) = manifest
+        .files
+        .keys()
+        .find(|f| filter.is_kept(f) && !files.contains(f))
+    {
+        return Err(format!(
+            "{missing} is in the manifest but not in the release archive"
+        ));
+    }
+
+    Ok(manifest)
+}
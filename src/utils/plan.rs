@@ -0,0 +1,125 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use tabled::{builder::Builder, settings::Style};
+
+use super::{
+    common::{format_size, list_files},
+    data::{is_plugin_data_path, PLUGIN_DATA_FOLDER},
+};
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Only show what would be changed instead of changing it, for every command from now on
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    Write,
+    Replace,
+    Delete,
+}
+
+/// A file a command would write or delete
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    pub path: String,
+    pub size: u64,
+    pub change: FileChange,
+}
+
+/// Plan installing the files of an archive into a plugin folder, replacing what is there
+///
+/// The plugin's data folder is never touched.
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder in `Plugins/`
+/// * `files` files in the archive with their sizes
+pub fn plan_install(plugin_path: &Path, files: Vec<(String, u64)>) -> Vec<PlannedFile> {
+    let installed = list_files(plugin_path);
+    let keeps_data = plugin_path.join(PLUGIN_DATA_FOLDER).is_dir();
+
+    let mut planned: Vec<PlannedFile> = files
+        .iter()
+        .filter(|(path, _)| !(keeps_data && is_plugin_data_path(path)))
+        .map(|(path, size)| PlannedFile {
+            path: path.clone(),
+            size: *size,
+            change: if installed.iter().any(|(p, _)| p == path) {
+                FileChange::Replace
+            } else {
+                FileChange::Write
+            },
+        })
+        .collect();
+
+    for (path, size) in installed {
+        if !is_plugin_data_path(&path) && !files.iter().any(|(p, _)| p == &path) {
+            planned.push(PlannedFile {
+                path,
+                size,
+                change: FileChange::Delete,
+            });
+        }
+    }
+
+    planned
+}
+
+/// Plan deleting a plugin folder
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder in `Plugins/`
+/// * `keep_data` whether the plugin's data folder is kept
+pub fn plan_remove(plugin_path: &Path, keep_data: bool) -> Vec<PlannedFile> {
+    list_files(plugin_path)
+        .into_iter()
+        .filter(|(path, _)| !(keep_data && is_plugin_data_path(path)))
+        .map(|(path, size)| PlannedFile {
+            path,
+            size,
+            change: FileChange::Delete,
+        })
+        .collect()
+}
+
+/// Format a plan as a table with a summary line
+///
+/// ### Arguments
+/// * `heading` what the plan is for
+/// * `files` files that would be written or deleted
+pub fn format_plan(heading: &str, files: &[PlannedFile]) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(["", "File", "Size"]);
+
+    for file in files {
+        let change = match file.change {
+            FileChange::Write => "+",
+            FileChange::Replace => "~",
+            FileChange::Delete => "-",
+        };
+        builder.push_record([change, &file.path, &format_size(file.size)]);
+    }
+
+    let total = |changes: &[FileChange]| -> (usize, u64) {
+        let matching = files.iter().filter(|f| changes.contains(&f.change));
+        (matching.clone().count(), matching.map(|f| f.size).sum())
+    };
+    let (written, written_size) = total(&[FileChange::Write, FileChange::Replace]);
+    let (deleted, deleted_size) = total(&[FileChange::Delete]);
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    format!(
+        "{heading}\n{table}\n{written} file(s) to write ({}), {deleted} to delete ({})",
+        format_size(written_size),
+        format_size(deleted_size)
+    )
+}
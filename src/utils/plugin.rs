@@ -1,157 +1,321 @@
+use pretty_duration::pretty_duration;
 use serde_json::Value;
 use tabled::{builder::Builder, settings::Style};
 
-use super::common::{Plugin, PluginPermission, ProjectFile};
+use super::{
+    common::{format_size, get_cpm_state_path, Plugin, PluginPermission},
+    data::{restore_plugin_data, StashedData, PLUGIN_DATA_FOLDER},
+    link::Link,
+    lua::{check_lua_files, check_requires},
+    progress::print_line,
+};
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
-    io::{Read, Write},
-    path::PathBuf,
+    io::Read,
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 
-/// Write plugin files to Plugins/ folder
+/// Check that a plugin folder name is a single folder, so it can't point outside `Plugins/`
+///
+/// ### Arguments
+/// * `name` plugin folder name, e.g. from a plugin index
+pub fn validate_plugin_folder_name(name: &str) -> Result<(), String> {
+    let mut components = Path::new(name).components();
+    let is_single_folder = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(folder)), None) if folder == name
+    );
+
+    // backslashes separate folders on Windows, so they are rejected everywhere
+    if !is_single_folder || name.contains(['/', '\\']) {
+        return Err(format!("Invalid plugin folder name '{name}'"));
+    }
+
+    Ok(())
+}
+
+/// Check that a plugin can be installed into Plugins/ folder, returning its path there
 ///
 /// ### Arguments
-/// * `base_path` path to Chatterino folder
+/// * `chatterino_path` path to Chatterino folder
 /// * `name` name of plugin to install (will be the folder name in `Plugins/`)
-/// * `files` plugin files
-pub fn write_plugin_data(
-    base_path: PathBuf,
+/// * `replace` whether an existing installation of the plugin will be replaced
+pub fn check_plugin_target(
+    chatterino_path: &Path,
     name: &str,
-    files: Vec<ProjectFile>,
-) -> Result<(), String> {
+    replace: bool,
+) -> Result<PathBuf, String> {
+    validate_plugin_folder_name(name)?;
+
+    let base_path = chatterino_path.join("Plugins");
     if !base_path.is_dir() {
         return Err("Plugins folder not found in Chatterino folder".to_string());
     }
 
-    let plugin_path = base_path.join(name);
-
     // check if a plugin with the same name is already installed
-    if plugin_path.is_dir() {
+    if base_path.join(name).is_dir() && !replace {
         return Err(format!(
             "A plugin with the name {name} is already installed."
         ));
     }
 
-    if fs::create_dir_all(&plugin_path).is_err() {
-        return Err(format!("There was an error creating {name}"));
+    Ok(base_path.join(name))
+}
+
+/// Prepare an empty staging folder to extract a plugin into before installing it
+///
+/// ### Arguments
+/// * `chatterino_path` path to Chatterino folder
+/// * `name` name of plugin to install (will be the folder name in `Plugins/`)
+/// * `replace` whether an existing installation of the plugin will be replaced
+pub fn create_staging_folder(
+    chatterino_path: &Path,
+    name: &str,
+    replace: bool,
+) -> Result<PathBuf, String> {
+    // the staging folder is emptied, so it must not point anywhere else either
+    validate_plugin_folder_name(name)?;
+    check_plugin_target(chatterino_path, name, replace)?;
+
+    let staging_path = get_cpm_state_path(chatterino_path)
+        .join("staging")
+        .join(name);
+    if staging_path.exists() {
+        fs::remove_dir_all(&staging_path).or(Err(format!("There was an error staging {name}")))?;
     }
-    println!("Wrote {}", &plugin_path.to_string_lossy());
 
-    for file in files {
-        let subpath = file.path.path_components.join("/");
-        let path = plugin_path.join(subpath);
+    Ok(staging_path)
+}
 
-        if file.path.is_dir {
-            if fs::create_dir_all(&path).is_err() {
-                return Err(format!(
-                    "There was an error creating {}",
-                    file.path.path_components.join("/")
-                ));
-            }
-        } else {
-            let f = File::create_new(&path);
-
-            if let Ok(mut f) = f {
-                if f.write_all(&file.content).is_err() {
-                    return Err(format!(
-                        "There was an writing to {}",
-                        file.path.path_components.join("/")
-                    ));
-                }
-            } else {
-                return Err(format!(
-                    "There was an error creating {}",
-                    file.path.path_components.join("/")
-                ));
-            }
+/// Move a staged plugin into Plugins/ folder
+///
+/// The plugin's data folder is carried over from the installation being replaced, or restored
+/// from a previous removal.
+///
+/// ### Arguments
+/// * `chatterino_path` path to Chatterino folder
+/// * `name` name of plugin to install (will be the folder name in `Plugins/`)
+pub fn install_staged_plugin(chatterino_path: &Path, name: &str) -> Result<(), String> {
+    let plugin_path = chatterino_path.join("Plugins").join(name);
+    let state_path = get_cpm_state_path(chatterino_path);
+    let staging_root = state_path.join("staging");
+    let staging_path = staging_root.join(name);
+    let staging_err_str = format!("There was an error staging {name}");
+
+    // the user's data always wins over a data folder shipped in the archive
+    let staged_data_path = staging_path.join(PLUGIN_DATA_FOLDER);
+    let installed_data_path = plugin_path.join(PLUGIN_DATA_FOLDER);
+    if installed_data_path.is_dir() {
+        if staged_data_path.exists() {
+            fs::remove_dir_all(&staged_data_path).or(Err(staging_err_str.clone()))?;
         }
-        println!("Wrote {}", path.to_string_lossy());
+        fs::rename(&installed_data_path, &staged_data_path).or(Err(format!(
+            "There was an error keeping the data of {name}"
+        )))?;
+    } else if restore_plugin_data(&state_path, name, &staged_data_path)? {
+        print_line(&format!(
+            "Restored data from a previous installation of {name}"
+        ));
     }
 
+    // swap the old installation out, putting it back if the new one can't be moved in
+    let old_path = staging_root.join(format!("{name}.old"));
+    if plugin_path.is_dir() {
+        if old_path.exists() {
+            fs::remove_dir_all(&old_path).or(Err(staging_err_str.clone()))?;
+        }
+        fs::rename(&plugin_path, &old_path)
+            .or(Err(format!("There was an error replacing {name}")))?;
+    }
+
+    if fs::rename(&staging_path, &plugin_path).is_err() {
+        if old_path.is_dir() {
+            let _ = fs::rename(&old_path, &plugin_path);
+        }
+        return Err(format!("There was an error moving {name} into Plugins/"));
+    }
+
+    if old_path.is_dir() {
+        fs::remove_dir_all(&old_path).or(Err(format!(
+            "There was an error removing the old version of {name}"
+        )))?;
+    }
+
+    print_line(&format!("Installed {}", plugin_path.to_string_lossy()));
+
     Ok(())
 }
 
+/// Check a plugin folder for everything that would stop Chatterino from loading it
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder
+pub fn validate_plugin_folder(plugin_path: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    match fs::read_to_string(plugin_path.join("info.json")) {
+        Ok(buf) => match serde_json::from_str::<Value>(&buf) {
+            Ok(json) => problems.extend(
+                validate_plugin_info(&json)
+                    .into_iter()
+                    .map(|p| format!("info.json: {p}")),
+            ),
+            Err(e) => problems.push(format!("info.json:{}:{}: {e}", e.line(), e.column())),
+        },
+        Err(_) => problems.push("info.json is missing".to_string()),
+    }
+
+    if !plugin_path.join("init.lua").is_file() {
+        problems.push("init.lua is missing".to_string());
+    }
+
+    problems.extend(check_lua_files(plugin_path));
+    problems.extend(check_requires(plugin_path));
+
+    problems
+}
+
 /// Get plugin metadata from folder
 ///
 /// ### Arguments
 /// * `plugin_path` path to plugin folder
 /// * `folder_name` plugin folder name
 pub fn parse_plugin(plugin_path: PathBuf, folder_name: String) -> Result<Option<Plugin>, String> {
-    let mut plugin = Plugin::new();
-
     let info_file_path = plugin_path.join("info.json");
     if !info_file_path.is_file() {
         return Ok(None);
     }
 
-    let mut info_file = File::open(info_file_path)
-        .or(Err("There was an error reading the info.json plugin file"))?;
+    let read_err_str = format!("There was an error reading {folder_name}/info.json");
+    let mut info_file = File::open(info_file_path).or(Err(read_err_str.clone()))?;
 
     let mut info_file_buf = Vec::new();
     info_file
         .read_to_end(&mut info_file_buf)
-        .or(Err("There was an error reading the info.json plugin file"))?;
+        .or(Err(read_err_str))?;
 
-    let buf: String = String::from_utf8(info_file_buf)
-        .or(Err("There was an error decoding the info.json plugin file"))?;
+    let buf: String = String::from_utf8(info_file_buf).or(Err(format!(
+        "There was an error decoding {folder_name}/info.json"
+    )))?;
 
     let json: Value = serde_json::from_str(buf.as_str())
-        .or(Err("There was an error parsing the info.json plugin file"))?;
+        .map_err(|e| format!("There was an error parsing {folder_name}/info.json: {e}"))?;
+
+    parse_plugin_info(folder_name, &json).map(Some)
+}
+
+/// Get plugin metadata from the contents of `info.json`
+///
+/// ### Arguments
+/// * `folder_name` plugin folder name
+/// * `json` parsed contents of `info.json`
+pub fn parse_plugin_info(folder_name: String, json: &Value) -> Result<Plugin, String> {
+    let field_err =
+        |field: &str, what: &str| format!("{folder_name}/info.json: '{field}' is not {what}");
+    let string = |field: &str| -> Result<Option<String>, String> {
+        match json.get(field) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(field_err(field, "a string")),
+        }
+    };
+    let strings = |field: &str| -> Result<Vec<String>, String> {
+        match json.get(field) {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(Value::Array(values)) => values
+                .iter()
+                .map(|v| v.as_str().map(|v| v.to_string()))
+                .collect::<Option<_>>()
+                .ok_or(field_err(field, "a list of strings")),
+            Some(_) => Err(field_err(field, "a list of strings")),
+        }
+    };
+
+    let mut plugin = Plugin::new();
+
+    plugin.name = string("name")?;
+    plugin.description = string("description")?;
+    plugin.homepage = string("homepage")?;
+    plugin.version = string("version")?;
+    plugin.licence = string("licence")?;
+    plugin.authors = strings("authors")?;
+    plugin.tags = strings("tags")?;
+
+    let permissions_err = field_err("permissions", "a list of objects with a 'type'");
+    plugin.permissions = match json.get("permissions") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|v| {
+                v.as_object()
+                    .and_then(|v| PluginPermission::from_map(v).ok())
+            })
+            .collect::<Option<_>>()
+            .ok_or(permissions_err)?,
+        Some(_) => return Err(permissions_err),
+    };
 
     plugin.folder = folder_name;
-    plugin.name = json
-        .get("name")
-        .map(|v| serde_json::from_value(v.clone()).unwrap());
-    plugin.description = json
-        .get("description")
-        .map(|v| serde_json::from_value(v.clone()).unwrap());
-    plugin.homepage = json
-        .get("homepage")
-        .map(|v| serde_json::from_value(v.clone()).unwrap());
-    plugin.version = json
-        .get("version")
-        .map(|v| serde_json::from_value(v.clone()).unwrap());
-    plugin.licence = json
-        .get("licence")
-        .map(|v| serde_json::from_value(v.clone()).unwrap());
-
-    let authors: Vec<Value> = Vec::new();
-    let authors: Vec<String> = json
-        .get("authors")
-        .map(|v| v.as_array())
-        .unwrap_or(None)
-        .unwrap_or(&authors)
-        .to_owned()
-        .iter()
-        .map(|v| serde_json::from_value(v.clone()).unwrap())
-        .collect();
-    plugin.authors = authors;
-
-    let tags: Vec<Value> = Vec::new();
-    let tags: Vec<String> = json
-        .get("tags")
-        .map(|v| v.as_array())
-        .unwrap_or(None)
-        .unwrap_or(&tags)
-        .to_owned()
-        .iter()
-        .map(|v| serde_json::from_value(v.clone()).unwrap())
-        .collect();
-    plugin.tags = tags;
-
-    let permissions: Vec<Value> = Vec::new();
-    let permissions: Vec<PluginPermission> = json
-        .get("permissions")
-        .map(|v| v.as_array())
-        .unwrap_or(None)
-        .unwrap_or(&permissions)
-        .to_owned()
-        .iter()
-        .map(|v| PluginPermission::from_map(v.as_object().unwrap()).unwrap())
-        .collect();
-    plugin.permissions = permissions;
-
-    Ok(Some(plugin))
+
+    Ok(plugin)
+}
+
+/// Permission types Chatterino knows about
+pub const PLUGIN_PERMISSION_TYPES: [&str; 3] = ["FilesystemRead", "FilesystemWrite", "Network"];
+
+/// Check plugin metadata for problems that stop it from being parsed or loaded
+///
+/// ### Arguments
+/// * `json` parsed contents of `info.json`
+pub fn validate_plugin_info(json: &Value) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let Some(info) = json.as_object() else {
+        problems.push("info.json is not an object".to_string());
+        return problems;
+    };
+
+    if !info.get("name").is_some_and(|v| v.is_string()) {
+        problems.push("'name' is missing or not a string".to_string());
+    }
+
+    for key in ["description", "homepage", "version", "licence"] {
+        if info.get(key).is_some_and(|v| !v.is_string()) {
+            problems.push(format!("'{key}' is not a string"));
+        }
+    }
+
+    for key in ["authors", "tags", "files"] {
+        let Some(value) = info.get(key) else {
+            continue;
+        };
+
+        if !value
+            .as_array()
+            .is_some_and(|a| a.iter().all(|v| v.is_string()))
+        {
+            problems.push(format!("'{key}' is not a list of strings"));
+        }
+    }
+
+    if let Some(permissions) = info.get("permissions") {
+        match permissions.as_array() {
+            Some(permissions) => {
+                for permission in permissions {
+                    match permission.get("type").and_then(|v| v.as_str()) {
+                        Some(type_) if PLUGIN_PERMISSION_TYPES.contains(&type_) => {}
+                        Some(type_) => problems.push(format!("unknown permission '{type_}'")),
+                        None => problems.push("permission without a 'type'".to_string()),
+                    }
+                }
+            }
+            None => problems.push("'permissions' is not a list".to_string()),
+        }
+    }
+
+    problems
 }
 
 /// Get all plugins metadata
@@ -165,32 +329,51 @@ pub fn parse_plugins(path: &PathBuf) -> Result<Vec<Plugin>, String> {
     let file_read_err_str = "Could not read file in Plugins/ folder";
     for entry in entries {
         let dir_entry = entry.or(Err(file_read_err_str))?;
-        let file_type = dir_entry.file_type().or(Err(file_read_err_str))?;
         let file_name = dir_entry.file_name().to_string_lossy().to_string();
 
-        if !file_type.is_dir() {
+        // linked plugins are symlinks to folders
+        let plugin_path = dir_entry.path();
+        if !plugin_path.is_dir() {
             continue;
         }
 
-        let plugin_path = dir_entry.path();
-
-        let plugin = parse_plugin(plugin_path, file_name)?;
-        match plugin {
-            Some(plugin) => plugins.push(plugin),
-            None => continue,
+        // one broken info.json shouldn't stop commands about every plugin
+        match parse_plugin(plugin_path, file_name) {
+            Ok(Some(plugin)) => plugins.push(plugin),
+            Ok(None) => continue,
+            Err(e) => print_line(&format!("Warning: {e}, skipping it")),
         };
     }
 
     Ok(plugins)
 }
 
-pub fn print_plugins(plugins: Vec<Plugin>) {
+/// Print installed plugins
+///
+/// ### Arguments
+/// * `plugins` installed plugins
+/// * `links` plugins linked with `cpm link` by folder name
+pub fn print_plugins(plugins: Vec<Plugin>, links: &BTreeMap<String, Link>) {
     let mut builder = Builder::default();
     builder.push_record(["Installation Name", "Plugin Name", "Version"]);
 
     for plugin in plugins {
+        let folder = match links.get(&plugin.folder) {
+            Some(link) if link.mirror => format!(
+                "{} (mirror of {})",
+                plugin.folder,
+                link.source.to_string_lossy()
+            ),
+            Some(link) => format!(
+                "{} (linked to {})",
+                plugin.folder,
+                link.source.to_string_lossy()
+            ),
+            None => plugin.folder,
+        };
+
         builder.push_record([
-            plugin.folder,
+            folder,
             format!("({})", plugin.name.unwrap_or("Unknown".to_string())),
             format!("v{}", plugin.version.unwrap_or("Unknown".to_string())),
         ]);
@@ -200,7 +383,12 @@ pub fn print_plugins(plugins: Vec<Plugin>) {
     println!("{table}");
 }
 
-pub fn print_plugin_info(plugin: Plugin) {
+/// Print plugin metadata
+///
+/// ### Arguments
+/// * `plugin` plugin metadata
+/// * `extra_rows` rows to add below the metadata, e.g. where the plugin came from
+pub fn print_plugin_info(plugin: Plugin, extra_rows: Vec<(String, String)>) {
     let mut builder = Builder::new();
     builder.push_record(["Folder".to_string(), plugin.folder]);
     builder.push_record([
@@ -234,7 +422,98 @@ pub fn print_plugin_info(plugin: Plugin) {
             .collect::<Vec<String>>()
             .join(", "),
     ]);
+    for (key, value) in extra_rows {
+        builder.push_record([key, value]);
+    }
 
     let table = builder.build().with(Style::ascii_rounded()).to_string();
     println!("{table}");
 }
+
+pub fn print_stashed_data(stashed: Vec<StashedData>) {
+    let mut builder = Builder::default();
+    builder.push_record(["Plugin", "Size", "Kept For"]);
+
+    for data in stashed {
+        let age = data
+            .stashed_at
+            .and_then(|t| SystemTime::now().duration_since(t).ok())
+            .map(|d| pretty_duration(&Duration::from_secs(d.as_secs()), None))
+            .unwrap_or("Unknown".to_string());
+
+        builder.push_record([data.folder, format_size(data.size), age]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(json: &str) -> Result<Plugin, String> {
+        parse_plugin_info("plugin".to_string(), &serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn parse_info() {
+        let plugin = info(
+            r#"{"name": "Plugin", "authors": ["a", "b"], "version": null,
+                "permissions": [{"type": "Network"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(plugin.folder, "plugin");
+        assert_eq!(plugin.name.as_deref(), Some("Plugin"));
+        assert_eq!(plugin.authors, ["a", "b"]);
+        assert_eq!(plugin.version, None);
+        assert_eq!(plugin.permissions[0].type_, "Network");
+    }
+
+    #[test]
+    fn parse_malformed_info() {
+        for (json, error) in [
+            (r#"{"name": 3}"#, "'name' is not a string"),
+            (r#"{"authors": "a"}"#, "'authors' is not a list of strings"),
+            (r#"{"tags": [1]}"#, "'tags' is not a list of strings"),
+            (
+                r#"{"permissions": [1]}"#,
+                "'permissions' is not a list of objects",
+            ),
+            (
+                r#"{"permissions": [{}]}"#,
+                "'permissions' is not a list of objects",
+            ),
+        ] {
+            let message = info(json).expect_err(json);
+            assert!(message.starts_with("plugin/info.json: "), "{message}");
+            assert!(message.contains(error), "{json}: {message}");
+        }
+    }
+
+    #[test]
+    fn valid_folder_names() {
+        for name in ["plugin", "my-plugin", "plugin.v2", "..plugin", "plugin 2"] {
+            assert!(validate_plugin_folder_name(name).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn invalid_folder_names() {
+        for name in [
+            "",
+            ".",
+            "..",
+            "../plugin",
+            "plugin/..",
+            "a/b",
+            "a\\b",
+            "..\\plugin",
+            "/plugin",
+            "plugin/",
+            "./plugin",
+        ] {
+            assert!(validate_plugin_folder_name(name).is_err(), "{name}");
+        }
+    }
+}
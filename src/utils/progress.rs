@@ -0,0 +1,153 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use pretty_duration::pretty_duration;
+use std::{
+    io::{stdout, IsTerminal},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Stop showing progress and per-file output, for every operation from now on
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+fn shows_bars() -> bool {
+    !is_quiet() && stdout().is_terminal()
+}
+
+/// All progress bars are drawn together, so plugins installed in parallel don't overwrite each
+/// other's progress
+fn add_bar(bar: ProgressBar) -> ProgressBar {
+    BARS.get_or_init(MultiProgress::new).add(bar)
+}
+
+/// Print a line above the progress bars being shown
+pub fn print_line(line: &str) {
+    match BARS.get() {
+        Some(bars) if shows_bars() => {
+            let _ = bars.println(line);
+        }
+        _ => println!("{line}"),
+    }
+}
+
+/// Progress of downloading and extracting a plugin
+///
+/// Shown as a progress bar on a terminal, as one line per extracted file otherwise, and not at
+/// all with `--quiet`.
+#[derive(Clone)]
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    name: String,
+    files: u64,
+}
+
+impl Progress {
+    pub fn new(name: &str) -> Self {
+        let bar = if shows_bars() {
+            let bar = add_bar(ProgressBar::new_spinner());
+            bar.set_style(
+                ProgressStyle::with_template("{spinner} {prefix} {bytes} downloaded, {msg}")
+                    .unwrap(),
+            );
+            bar.set_prefix(name.to_string());
+            bar.set_message("0 files extracted");
+            Some(bar)
+        } else {
+            None
+        };
+
+        Progress {
+            bar,
+            name: name.to_string(),
+            files: 0,
+        }
+    }
+
+    /// Set the size of the download once it is known
+    pub fn set_download_size(&self, size: u64) {
+        if let Some(bar) = &self.bar {
+            bar.set_length(size);
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{prefix} [{bar:30}] {bytes}/{total_bytes} downloaded, {msg}",
+                )
+                .unwrap()
+                .progress_chars("=> "),
+            );
+        }
+    }
+
+    pub fn downloaded(&self, bytes: u64) {
+        if let Some(bar) = &self.bar {
+            bar.inc(bytes);
+        }
+    }
+
+    pub fn extracted(&mut self, path: &str) {
+        self.files += 1;
+
+        match &self.bar {
+            Some(bar) => bar.set_message(format!("{} files extracted", self.files)),
+            None if !is_quiet() => print_line(&format!("Wrote {path}")),
+            None => {}
+        }
+    }
+
+    /// Stop showing progress without reporting anything, e.g. after an error
+    pub fn clear(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+        if !is_quiet() {
+            print_line(&format!("Extracted {} files for {}", self.files, self.name));
+        }
+    }
+}
+
+/// Wait, counting down the time left on a terminal
+///
+/// ### Arguments
+/// * `duration` how long to wait
+/// * `reason` why cpm is waiting
+pub fn wait_with_countdown(duration: Duration, reason: &str) {
+    let total = pretty_duration(&Duration::from_secs(duration.as_secs()), None);
+
+    if !shows_bars() {
+        print_line(&format!("{reason}, waiting {total}"));
+        sleep(duration);
+        return;
+    }
+
+    let bar = add_bar(ProgressBar::new_spinner());
+    bar.set_style(ProgressStyle::with_template("{spinner} {msg}").unwrap());
+
+    let end = Instant::now() + duration;
+    while let Some(left) = end.checked_duration_since(Instant::now()) {
+        bar.set_message(format!(
+            "{reason}, waiting {}",
+            pretty_duration(&Duration::from_secs(left.as_secs() + 1), None)
+        ));
+        bar.tick();
+        sleep(left.min(Duration::from_secs(1)));
+    }
+
+    bar.finish_and_clear();
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Where an installed plugin came from, recorded by cpm at install time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    pub source: String,
+    pub owner: String,
+    pub repo: String,
+    #[serde(rename = "ref")]
+    pub ref_: String,
+    pub commit: String,
+    pub installed_at: u64,
+    /// Name of the plugin in the plugin index, if it was installed from there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_name: Option<String>,
+    /// Release asset the plugin was installed from, instead of the repository's tarball
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+    /// Patterns given with `--exclude`, left out again on updates
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// sha256 of every installed file except the data folder, for `cpm verify`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+}
+
+impl Provenance {
+    pub fn new(owner: &str, repo: &str, git_ref: &str, commit: &str) -> Self {
+        Provenance {
+            source: format!("https://github.com/{owner}/{repo}"),
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            ref_: git_ref.to_string(),
+            commit: commit.to_string(),
+            installed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            index_name: None,
+            asset: None,
+            exclude: Vec::new(),
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+fn provenance_path(state_path: &Path, folder: &str) -> PathBuf {
+    state_path.join("provenance").join(format!("{folder}.json"))
+}
+
+/// Read the recorded provenance of a plugin, if cpm installed it
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+/// * `folder` plugin folder name
+pub fn read_provenance(state_path: &Path, folder: &str) -> Result<Option<Provenance>, String> {
+    let path = provenance_path(state_path, folder);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let buf = fs::read_to_string(path).or(Err(format!(
+        "There was an error reading the provenance of {folder}"
+    )))?;
+    let provenance = serde_json::from_str(&buf).or(Err(format!(
+        "There was an error parsing the provenance of {folder}"
+    )))?;
+
+    Ok(Some(provenance))
+}
+
+/// Record the provenance of a plugin
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+/// * `folder` plugin folder name
+/// * `provenance` where the plugin came from
+pub fn write_provenance(
+    state_path: &Path,
+    folder: &str,
+    provenance: &Provenance,
+) -> Result<(), String> {
+    let path = provenance_path(state_path, folder);
+    let err_str = format!("There was an error writing the provenance of {folder}");
+
+    fs::create_dir_all(path.parent().unwrap()).or(Err(err_str.clone()))?;
+    let buf = serde_json::to_string_pretty(provenance).or(Err(err_str.clone()))?;
+    fs::write(path, buf).or(Err(err_str))?;
+
+    Ok(())
+}
+
+/// Forget the provenance of a plugin
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+/// * `folder` plugin folder name
+pub fn remove_provenance(state_path: &Path, folder: &str) -> Result<(), String> {
+    let path = provenance_path(state_path, folder);
+    if path.is_file() {
+        fs::remove_file(path).or(Err(format!(
+            "There was an error removing the provenance of {folder}"
+        )))?;
+    }
+
+    Ok(())
+}
+
+/// Get the folder names of all plugins cpm has recorded provenance for
+///
+/// ### Arguments
+/// * `state_path` path to cpm's folder inside the Chatterino folder
+pub fn list_provenance(state_path: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(state_path.join("provenance")) else {
+        return Vec::new();
+    };
+
+    let mut folders: Vec<String> = entries
+        .flatten()
+        .filter_map(|e| {
+            e.file_name()
+                .to_string_lossy()
+                .strip_suffix(".json")
+                .map(|f| f.to_string())
+        })
+        .collect();
+    folders.sort();
+
+    folders
+}
@@ -0,0 +1,710 @@
+use mlua::{
+    Function, HookTriggers, Lua, MultiValue, RegistryKey, Table, UserData, UserDataMethods, Value,
+    Variadic,
+};
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+use tabled::{builder::Builder, settings::Style};
+
+use super::{common::PluginPermission, data::PLUGIN_DATA_FOLDER};
+
+/// Instructions a single step of a plugin may run before it's considered stuck
+const INSTRUCTION_LIMIT: u64 = 100_000_000;
+
+/// How often the instruction limit is checked
+const INSTRUCTION_CHECK_INTERVAL: u32 = 10_000;
+
+/// Callbacks scheduled with `c2.later` that run after each step before the rest are dropped
+const MAX_TIMERS: usize = 1000;
+
+/// Names of `c2.LogLevel` values
+const LOG_LEVELS: [&str; 4] = ["Debug", "Info", "Warning", "Critical"];
+
+/// Names of `c2.EventType` values
+const EVENT_TYPES: [&str; 1] = ["CompletionRequested"];
+
+/// Names of `c2.ChannelType` values
+const CHANNEL_TYPES: [&str; 11] = [
+    "None",
+    "Direct",
+    "Twitch",
+    "TwitchWhispers",
+    "TwitchWatching",
+    "TwitchMentions",
+    "TwitchLive",
+    "TwitchAutomod",
+    "TwitchEnd",
+    "Irc",
+    "Misc",
+];
+
+/// Names of `c2.HTTPMethod` values
+const HTTP_METHODS: [&str; 5] = ["Get", "Post", "Put", "Delete", "Patch"];
+
+/// Functions Chatterino removes from the standard library, `io` is replaced as a whole
+const REMOVED_FUNCTIONS: [(&str, &str); 7] = [
+    ("os", "execute"),
+    ("os", "exit"),
+    ("os", "getenv"),
+    ("os", "remove"),
+    ("os", "rename"),
+    ("os", "tmpname"),
+    ("package", "loadlib"),
+];
+
+/// `io` functions that don't take a path, kept as they are
+const IO_PASSTHROUGH_FUNCTIONS: [&str; 5] = ["close", "flush", "read", "type", "write"];
+
+#[derive(Default)]
+struct State {
+    commands: Vec<String>,
+    callbacks: Vec<String>,
+    /// Callbacks from `c2.later` with their delay in milliseconds
+    timers: Vec<(u64, RegistryKey)>,
+    instructions: u64,
+}
+
+/// Stand-in for a Chatterino channel that prints what the plugin does with it
+struct MockChannel {
+    name: String,
+}
+
+impl UserData for MockChannel {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("get_name", |_, this, ()| Ok(this.name.clone()));
+        methods.add_method("get_display_name", |_, this, ()| Ok(this.name.clone()));
+        methods.add_method("get_type", |_, _, ()| Ok(2));
+        methods.add_method("is_twitch_channel", |_, _, ()| Ok(true));
+        methods.add_method("is_valid", |_, _, ()| Ok(true));
+        methods.add_method(
+            "send_message",
+            |_, this, (message, _execute_commands): (String, Option<bool>)| {
+                println!("  #{} > {message}", this.name);
+                Ok(())
+            },
+        );
+        methods.add_method("add_system_message", |_, this, message: String| {
+            println!("  #{} system: {message}", this.name);
+            Ok(())
+        });
+    }
+}
+
+/// Stand-in for a Chatterino HTTP request that prints it instead of sending it
+struct MockRequest {
+    method: String,
+    url: String,
+}
+
+impl UserData for MockRequest {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        for name in ["on_success", "on_error", "finally"] {
+            methods.add_method(name, |_, _, _callback: Function| Ok(()));
+        }
+        methods.add_method("set_timeout", |_, _, _timeout: u64| Ok(()));
+        methods.add_method("set_payload", |_, _, _payload: String| Ok(()));
+        methods.add_method("set_header", |_, _, _header: (String, String)| Ok(()));
+        methods.add_method("execute", |_, this, ()| {
+            println!("  HTTP {} {} (not sent)", this.method, this.url);
+            Ok(())
+        });
+    }
+}
+
+/// What a plugin may do outside of Lua, from the permissions in its `info.json`
+struct Sandbox {
+    /// The plugin folder, which modules are required from
+    plugin_path: PathBuf,
+    /// The plugin's data folder, which all file paths are resolved in
+    data_path: PathBuf,
+    filesystem_read: bool,
+    filesystem_write: bool,
+    network: bool,
+}
+
+impl Sandbox {
+    fn new(plugin_path: &Path, permissions: &[PluginPermission]) -> Self {
+        let has = |type_: &str| permissions.iter().any(|p| p.type_ == type_);
+
+        Sandbox {
+            plugin_path: plugin_path.to_path_buf(),
+            data_path: plugin_path.join(PLUGIN_DATA_FOLDER),
+            filesystem_read: has("FilesystemRead"),
+            filesystem_write: has("FilesystemWrite"),
+            network: has("Network"),
+        }
+    }
+
+    fn check_permission(allowed: bool, permission: &str) -> mlua::Result<()> {
+        if allowed {
+            Ok(())
+        } else {
+            Err(mlua::Error::runtime(format!(
+                "the plugin does not have the {permission} permission in its info.json"
+            )))
+        }
+    }
+
+    /// Resolve a path the plugin gave to `io` like Chatterino does, in its data folder
+    ///
+    /// ### Arguments
+    /// * `path` path relative to the data folder, or an absolute path inside it
+    /// * `read` whether the file is read
+    /// * `write` whether the file is written
+    fn resolve(&self, path: &str, read: bool, write: bool) -> mlua::Result<String> {
+        if read {
+            Self::check_permission(self.filesystem_read, "FilesystemRead")?;
+        }
+        if write {
+            Self::check_permission(self.filesystem_write, "FilesystemWrite")?;
+        }
+
+        let outside =
+            || mlua::Error::runtime(format!("'{path}' is outside of the plugin's data folder"));
+
+        let path = Path::new(path);
+        let relative = if path.has_root() {
+            path.strip_prefix(&self.data_path).map_err(|_| outside())?
+        } else {
+            path
+        };
+
+        let mut resolved = self.data_path.clone();
+        let mut depth = 0;
+        for component in relative.components() {
+            match component {
+                Component::Normal(part) => {
+                    resolved.push(part);
+                    depth += 1;
+                }
+                Component::CurDir => {}
+                Component::ParentDir if depth > 0 => {
+                    resolved.pop();
+                    depth -= 1;
+                }
+                _ => return Err(outside()),
+            }
+        }
+
+        if write {
+            fs::create_dir_all(&self.data_path).map_err(|e| {
+                mlua::Error::runtime(format!("could not create the data folder: {e}"))
+            })?;
+        }
+
+        // a symlink in the data folder could still point outside of it
+        if let Ok(root) = self.data_path.canonicalize() {
+            let existing = resolved
+                .ancestors()
+                .take_while(|p| p.starts_with(&self.data_path))
+                .find(|p| p.symlink_metadata().is_ok());
+            if let Some(existing) = existing {
+                if !existing.canonicalize().is_ok_and(|p| p.starts_with(&root)) {
+                    return Err(outside());
+                }
+            }
+        }
+
+        Ok(resolved.to_string_lossy().to_string())
+    }
+
+    /// Find the file of a module the plugin requires, `a.b` is `a/b.lua` or `a/b/init.lua`
+    ///
+    /// ### Arguments
+    /// * `name` module name given to `require`
+    fn find_module(&self, name: &str) -> mlua::Result<PathBuf> {
+        let not_found =
+            || mlua::Error::runtime(format!("module '{name}' not found in the plugin folder"));
+
+        let mut relative = PathBuf::new();
+        for part in name.split('.') {
+            let mut components = Path::new(part).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(p)), None) if p == part => relative.push(part),
+                _ => return Err(not_found()),
+            }
+        }
+
+        // a symlink in the plugin folder could still point outside of it
+        let root = self.plugin_path.canonicalize().map_err(|_| not_found())?;
+        [relative.with_extension("lua"), relative.join("init.lua")]
+            .into_iter()
+            .map(|p| self.plugin_path.join(p))
+            .find(|p| p.is_file() && p.canonicalize().is_ok_and(|p| p.starts_with(&root)))
+            .ok_or_else(not_found)
+    }
+}
+
+/// Call a function of the standard library Chatterino wraps, saved before it was replaced
+fn call_original<'lua>(
+    lua: &'lua Lua,
+    library: &str,
+    name: &str,
+    args: impl mlua::IntoLuaMulti<'lua>,
+) -> mlua::Result<MultiValue<'lua>> {
+    let original: Table = lua.named_registry_value(&format!("cpm_original_{library}"))?;
+    original.get::<_, Function>(name)?.call(args)
+}
+
+/// Create the `io` library Chatterino gives plugins, which only opens files in their data folder
+fn create_io<'lua>(lua: &'lua Lua, sandbox: &Rc<Sandbox>) -> mlua::Result<Table<'lua>> {
+    let original: Table = lua.globals().get("io")?;
+    lua.set_named_registry_value("cpm_original_io", &original)?;
+
+    let io = lua.create_table()?;
+    for name in IO_PASSTHROUGH_FUNCTIONS {
+        io.set(name, original.get::<_, Function>(name)?)?;
+    }
+
+    let open_sandbox = sandbox.clone();
+    io.set(
+        "open",
+        lua.create_function(move |lua, (path, mode): (String, Option<String>)| {
+            let mode = mode.unwrap_or("r".to_string());
+            let read = mode.contains(['r', '+']);
+            let write = mode.contains(['w', 'a', '+']);
+            let path = open_sandbox.resolve(&path, read, write)?;
+            call_original(lua, "io", "open", (path, mode))
+        })?,
+    )?;
+
+    let lines_sandbox = sandbox.clone();
+    io.set(
+        "lines",
+        lua.create_function(
+            move |lua, (path, formats): (Option<String>, Variadic<Value>)| match path {
+                Some(path) => {
+                    let path = lines_sandbox.resolve(&path, true, false)?;
+                    call_original(lua, "io", "lines", (path, formats))
+                }
+                None => call_original(lua, "io", "lines", formats),
+            },
+        )?,
+    )?;
+
+    for (name, read, write) in [("input", true, false), ("output", false, true)] {
+        let sandbox = sandbox.clone();
+        io.set(
+            name,
+            lua.create_function(move |lua, file: Value| match file {
+                Value::String(path) => {
+                    let path = sandbox.resolve(path.to_str()?, read, write)?;
+                    call_original(lua, "io", name, path)
+                }
+                file => call_original(lua, "io", name, file),
+            })?,
+        )?;
+    }
+
+    Ok(io)
+}
+
+/// Replace `loadfile` and `dofile` with ones that only load files from the data folder
+fn replace_file_loaders(lua: &Lua, sandbox: &Rc<Sandbox>) -> mlua::Result<()> {
+    let globals = lua.globals();
+    let original = lua.create_table()?;
+    original.set("loadfile", globals.get::<_, Function>("loadfile")?)?;
+    original.set("dofile", globals.get::<_, Function>("dofile")?)?;
+    lua.set_named_registry_value("cpm_original_base", original)?;
+
+    // reading code from stdin makes no sense in a plugin, so a path is required
+    let loadfile_sandbox = sandbox.clone();
+    globals.set(
+        "loadfile",
+        lua.create_function(move |lua, (path, rest): (String, Variadic<Value>)| {
+            let path = loadfile_sandbox.resolve(&path, true, false)?;
+            call_original(lua, "base", "loadfile", (path, rest))
+        })?,
+    )?;
+
+    let dofile_sandbox = sandbox.clone();
+    globals.set(
+        "dofile",
+        lua.create_function(move |lua, path: String| {
+            let path = dofile_sandbox.resolve(&path, true, false)?;
+            call_original(lua, "base", "dofile", path)
+        })?,
+    )?;
+
+    Ok(())
+}
+
+/// Replace `require` with one that only loads modules from the plugin folder
+///
+/// `package.path` and the searchers are ignored, so changing them can't load other files.
+fn replace_require(lua: &Lua, sandbox: &Rc<Sandbox>) -> mlua::Result<()> {
+    let package: Table = lua.globals().get("package")?;
+    lua.set_named_registry_value("cpm_loaded", package.get::<_, Table>("loaded")?)?;
+    package.set("searchers", Value::Nil)?;
+    package.set("searchpath", Value::Nil)?;
+
+    let require_sandbox = sandbox.clone();
+    lua.globals().set(
+        "require",
+        lua.create_function(move |lua, name: String| {
+            let loaded: Table = lua.named_registry_value("cpm_loaded")?;
+            let module: Value = loaded.get(name.as_str())?;
+            if !module.is_nil() {
+                return Ok(module);
+            }
+
+            let path = require_sandbox.find_module(&name)?;
+            let source = fs::read(&path).map_err(|e| {
+                mlua::Error::runtime(format!("could not read module '{name}': {e}"))
+            })?;
+            let chunk_name = path
+                .strip_prefix(&require_sandbox.plugin_path)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let module: Value = lua
+                .load(source.as_slice())
+                .set_name(format!("@{chunk_name}"))
+                .call((name.as_str(), path.to_string_lossy()))?;
+
+            // like the standard require, a module returning nothing is stored as true
+            if !module.is_nil() {
+                loaded.set(name.as_str(), module)?;
+            } else if loaded.get::<_, Value>(name.as_str())?.is_nil() {
+                loaded.set(name.as_str(), true)?;
+            }
+            loaded.get(name.as_str())
+        })?,
+    )?;
+
+    Ok(())
+}
+
+/// A plugin loaded into an embedded Lua VM with a mock of Chatterino's `c2` module
+pub struct PluginRuntime {
+    lua: Lua,
+    state: Rc<RefCell<State>>,
+}
+
+/// Create a table of enum values, numbered in order like Chatterino's
+fn create_enum<'lua>(lua: &'lua Lua, names: &[&str]) -> mlua::Result<Table<'lua>> {
+    let table = lua.create_table()?;
+    for (i, name) in names.iter().enumerate() {
+        table.set(*name, i)?;
+    }
+
+    Ok(table)
+}
+
+/// Create the mock `c2` module
+fn create_c2<'lua>(
+    lua: &'lua Lua,
+    state: &Rc<RefCell<State>>,
+    sandbox: &Rc<Sandbox>,
+) -> mlua::Result<Table<'lua>> {
+    let c2 = lua.create_table()?;
+    c2.set("LogLevel", create_enum(lua, &LOG_LEVELS)?)?;
+    c2.set("EventType", create_enum(lua, &EVENT_TYPES)?)?;
+    c2.set("ChannelType", create_enum(lua, &CHANNEL_TYPES)?)?;
+    c2.set("HTTPMethod", create_enum(lua, &HTTP_METHODS)?)?;
+
+    lua.set_named_registry_value("cpm_commands", lua.create_table()?)?;
+    lua.set_named_registry_value("cpm_callbacks", lua.create_table()?)?;
+
+    let command_state = state.clone();
+    c2.set(
+        "register_command",
+        lua.create_function(move |lua, (name, handler): (String, Function)| {
+            let commands: Table = lua.named_registry_value("cpm_commands")?;
+            if commands.contains_key(name.as_str())? {
+                return Ok(false);
+            }
+            commands.set(name.as_str(), handler)?;
+            command_state.borrow_mut().commands.push(name);
+            Ok(true)
+        })?,
+    )?;
+
+    let callback_state = state.clone();
+    c2.set(
+        "register_callback",
+        lua.create_function(move |lua, (event, handler): (usize, Function)| {
+            let Some(name) = EVENT_TYPES.get(event) else {
+                return Err(mlua::Error::runtime(format!("unknown event type {event}")));
+            };
+            let callbacks: Table = lua.named_registry_value("cpm_callbacks")?;
+            callbacks.set(event, handler)?;
+            callback_state.borrow_mut().callbacks.push(name.to_string());
+            Ok(())
+        })?,
+    )?;
+
+    c2.set(
+        "log",
+        lua.create_function(|lua, (level, values): (usize, mlua::Variadic<Value>)| {
+            let tostring: Function = lua.globals().get("tostring")?;
+            let message = values
+                .into_iter()
+                .map(|v| tostring.call::<_, String>(v))
+                .collect::<mlua::Result<Vec<_>>>()?
+                .join(" ");
+            let level = LOG_LEVELS.get(level).unwrap_or(&"Log");
+            println!("  [{}] {message}", level.to_lowercase());
+            Ok(())
+        })?,
+    )?;
+
+    let timer_state = state.clone();
+    c2.set(
+        "later",
+        lua.create_function(move |lua, (callback, delay): (Function, u64)| {
+            let key = lua.create_registry_value(callback)?;
+            timer_state.borrow_mut().timers.push((delay, key));
+            Ok(())
+        })?,
+    )?;
+
+    let channel = lua.create_table()?;
+    channel.set(
+        "by_name",
+        lua.create_function(|_, name: String| Ok(MockChannel { name }))?,
+    )?;
+    channel.set(
+        "by_twitch_id",
+        lua.create_function(|_, id: String| Ok(MockChannel { name: id }))?,
+    )?;
+    c2.set("Channel", channel)?;
+
+    let request_sandbox = sandbox.clone();
+    let request = lua.create_table()?;
+    request.set(
+        "create",
+        lua.create_function(move |_, (method, url): (usize, String)| {
+            Sandbox::check_permission(request_sandbox.network, "Network")?;
+            Ok(MockRequest {
+                method: HTTP_METHODS.get(method).unwrap_or(&"Get").to_uppercase(),
+                url,
+            })
+        })?,
+    )?;
+    c2.set("HTTPRequest", request)?;
+
+    Ok(c2)
+}
+
+impl PluginRuntime {
+    /// Load a plugin's `init.lua` and run the callbacks it schedules with `c2.later`
+    ///
+    /// ### Arguments
+    /// * `plugin_path` path to the plugin folder
+    /// * `permissions` permissions from the plugin's `info.json`
+    pub fn load(plugin_path: &Path, permissions: &[PluginPermission]) -> Result<Self, String> {
+        let source = fs::read(plugin_path.join("init.lua")).or(Err("Could not read init.lua"))?;
+
+        let lua = Lua::new();
+        let state = Rc::new(RefCell::new(State::default()));
+        let sandbox = Rc::new(Sandbox::new(plugin_path, permissions));
+        Self::setup(&lua, plugin_path, &state, &sandbox)
+            .or(Err("There was an error setting up the Lua runtime"))?;
+
+        let hook_state = state.clone();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(INSTRUCTION_CHECK_INTERVAL),
+            move |_, _| {
+                let mut state = hook_state.borrow_mut();
+                state.instructions += INSTRUCTION_CHECK_INTERVAL as u64;
+                if state.instructions > INSTRUCTION_LIMIT {
+                    return Err(mlua::Error::runtime(
+                        "instruction limit reached, stuck in a loop?",
+                    ));
+                }
+                Ok(())
+            },
+        );
+
+        let runtime = PluginRuntime { lua, state };
+        runtime.step(|lua| lua.load(source.as_slice()).set_name("@init.lua").exec())?;
+
+        Ok(runtime)
+    }
+
+    fn setup(
+        lua: &Lua,
+        plugin_path: &Path,
+        state: &Rc<RefCell<State>>,
+        sandbox: &Rc<Sandbox>,
+    ) -> mlua::Result<()> {
+        let globals = lua.globals();
+
+        let package: Table = globals.get("package")?;
+        let root = plugin_path.to_string_lossy();
+        package.set("path", format!("{root}/?.lua;{root}/?/init.lua"))?;
+        package.set("cpath", "")?;
+
+        for (library, function) in REMOVED_FUNCTIONS {
+            let table: Table = globals.get(library)?;
+            table.set(function, Value::Nil)?;
+        }
+
+        let loaded: Table = package.get("loaded")?;
+        let io = create_io(lua, sandbox)?;
+        loaded.set("io", &io)?;
+        globals.set("io", io)?;
+        replace_file_loaders(lua, sandbox)?;
+        replace_require(lua, sandbox)?;
+
+        let c2 = create_c2(lua, state, sandbox)?;
+        loaded.set("c2", &c2)?;
+        globals.set("c2", c2)?;
+
+        Ok(())
+    }
+
+    /// Run plugin code, then the callbacks it scheduled
+    fn step(&self, run: impl FnOnce(&Lua) -> mlua::Result<()>) -> Result<(), String> {
+        self.state.borrow_mut().instructions = 0;
+        run(&self.lua).map_err(|e| e.to_string())?;
+
+        let mut ran = 0;
+        loop {
+            let timers = std::mem::take(&mut self.state.borrow_mut().timers);
+            if timers.is_empty() {
+                return Ok(());
+            }
+
+            let mut timers = timers;
+            timers.sort_by_key(|(delay, _)| *delay);
+            for (delay, key) in timers {
+                if ran == MAX_TIMERS {
+                    println!("  Stopped after {MAX_TIMERS} c2.later callbacks");
+                    return Ok(());
+                }
+                ran += 1;
+
+                let callback: Function =
+                    self.lua.registry_value(&key).map_err(|e| e.to_string())?;
+                self.state.borrow_mut().instructions = 0;
+                callback
+                    .call::<_, ()>(())
+                    .map_err(|e| format!("in a c2.later callback after {delay}ms: {e}"))?;
+            }
+        }
+    }
+
+    /// Get the names of the commands the plugin registered, in order
+    pub fn commands(&self) -> Vec<String> {
+        self.state.borrow().commands.clone()
+    }
+
+    /// Get the events the plugin registered callbacks for, in order
+    pub fn callbacks(&self) -> Vec<String> {
+        self.state.borrow().callbacks.clone()
+    }
+
+    /// Run a registered command as if it was typed into a channel
+    ///
+    /// ### Arguments
+    /// * `line` the command with its arguments, e.g. `/tr en hello`
+    /// * `channel` name of the channel it's typed into
+    pub fn run_command(&self, line: &str, channel: &str) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some(name) = words.first() else {
+            return Err("No command given".to_string());
+        };
+
+        let handler: Option<Function> = self
+            .lua
+            .named_registry_value::<Table>("cpm_commands")
+            .and_then(|commands| commands.get(*name))
+            .map_err(|e| e.to_string())?;
+        let Some(handler) = handler else {
+            return Err(format!("The plugin did not register the command {name}"));
+        };
+
+        self.step(|lua| {
+            let context = lua.create_table()?;
+            context.set("words", lua.create_sequence_from(words.iter().copied())?)?;
+            context.set(
+                "channel",
+                MockChannel {
+                    name: channel.to_string(),
+                },
+            )?;
+            handler.call::<_, ()>(context)
+        })
+    }
+}
+
+/// Print the commands and callbacks a plugin registered
+///
+/// ### Arguments
+/// * `commands` names of the registered commands
+/// * `callbacks` events callbacks were registered for
+pub fn print_registrations(commands: &[String], callbacks: &[String]) {
+    let mut builder = Builder::default();
+    builder.push_record(["Type", "Name"]);
+
+    for command in commands {
+        builder.push_record(["Command", command]);
+    }
+    for callback in callbacks {
+        builder.push_record(["Callback", callback]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a plugin folder with an `init.lua` next to a module outside of it
+    fn create_plugin(name: &str, init_lua: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("cpm-runtime-{}-{name}", std::process::id()));
+        let plugin_path = root.join("plugin");
+        fs::create_dir_all(plugin_path.join("lib")).unwrap();
+        fs::write(plugin_path.join("init.lua"), init_lua).unwrap();
+        fs::write(plugin_path.join("lib/util.lua"), "return { answer = 42 }").unwrap();
+        fs::write(root.join("outside.lua"), "return 'escaped'").unwrap();
+
+        plugin_path
+    }
+
+    fn load(name: &str, init_lua: &str) -> Result<PluginRuntime, String> {
+        let plugin_path = create_plugin(name, init_lua);
+        let result = PluginRuntime::load(&plugin_path, &[]);
+        fs::remove_dir_all(plugin_path.parent().unwrap()).unwrap();
+        result
+    }
+
+    #[test]
+    fn require_from_plugin_folder() {
+        let init_lua = "assert(require('lib.util').answer == 42)\n\
+                        assert(require('lib.util') == require('lib.util'))";
+        assert!(load("inside", init_lua).is_ok());
+    }
+
+    #[test]
+    fn require_outside_plugin_folder() {
+        let outside =
+            std::env::temp_dir().join(format!("cpm-runtime-{}-outside", std::process::id()));
+        let absolute = format!(
+            "package.path = '{}/?.lua'\nrequire('outside')",
+            outside.display()
+        );
+        for init_lua in [
+            absolute.as_str(),
+            "package.path = '../?.lua;' .. package.path\nrequire('outside')",
+            "require('..outside')",
+            "require('../outside')",
+        ] {
+            let result = load("outside", init_lua).err().unwrap_or_default();
+            assert!(
+                result.contains("not found in the plugin folder"),
+                "{init_lua}: {result}"
+            );
+        }
+
+        assert!(load("outside", "package.searchers[2]('outside')").is_err());
+    }
+}
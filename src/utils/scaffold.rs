@@ -0,0 +1,236 @@
+use serde::Serialize;
+use std::{fs, path::Path};
+
+use super::common::Plugin;
+
+/// Version new plugins start at
+const NEW_PLUGIN_VERSION: &str = "0.1.0";
+
+/// Folder with LuaLS annotations for the `c2` API
+const META_FOLDER: &str = "meta";
+
+const GITIGNORE: &str = "# created by Chatterino for the plugin to store its files
+data/
+";
+
+const LUARC_JSON: &str = r#"{
+  "$schema": "https://raw.githubusercontent.com/LuaLS/vscode-lua/master/setting/schema.json",
+  "runtime.version": "Lua 5.4",
+  "workspace.library": ["meta"],
+  "diagnostics.globals": ["c2"]
+}
+"#;
+
+const C2_META: &str = r#"---@meta
+-- LuaLS annotations for the Chatterino plugin API, this file is not run
+
+---@class c2
+c2 = {}
+
+---@enum c2.LogLevel
+c2.LogLevel = { Debug = 0, Info = 1, Warning = 2, Critical = 3 }
+
+---@enum c2.EventType
+c2.EventType = { CompletionRequested = 0 }
+
+---@enum c2.ChannelType
+c2.ChannelType = {
+    None = 0,
+    Direct = 1,
+    Twitch = 2,
+    TwitchWhispers = 3,
+    TwitchWatching = 4,
+    TwitchMentions = 5,
+    TwitchLive = 6,
+    TwitchAutomod = 7,
+    TwitchEnd = 8,
+    Irc = 9,
+    Misc = 10,
+}
+
+---@class c2.Channel
+c2.Channel = {}
+
+---@param name string
+---@return c2.Channel?
+function c2.Channel.by_name(name) end
+
+---@param id string
+---@return c2.Channel?
+function c2.Channel.by_twitch_id(id) end
+
+---@return string
+function c2.Channel:get_name() end
+
+---@return string
+function c2.Channel:get_display_name() end
+
+---@return c2.ChannelType
+function c2.Channel:get_type() end
+
+---@return boolean
+function c2.Channel:is_twitch_channel() end
+
+---@return boolean
+function c2.Channel:is_valid() end
+
+---@param message string
+---@param execute_commands boolean?
+function c2.Channel:send_message(message, execute_commands) end
+
+---@param message string
+function c2.Channel:add_system_message(message) end
+
+---@class CommandContext
+---@field words string[] the command and its arguments
+---@field channel c2.Channel channel the command was run in
+
+---@class CompletionEvent
+---@field query string
+---@field full_text_content string
+---@field cursor_position integer
+---@field is_first_word boolean
+
+---@class CompletionList
+---@field values string[]
+---@field hide_others boolean
+
+---Register a command, returns false if the name is already taken
+---@param name string
+---@param handler fun(ctx: CommandContext)
+---@return boolean
+function c2.register_command(name, handler) end
+
+---@param type c2.EventType
+---@param handler fun(event: CompletionEvent): CompletionList
+function c2.register_callback(type, handler) end
+
+---@param level c2.LogLevel
+---@param ... any
+function c2.log(level, ...) end
+
+---Run a function after a delay
+---@param callback fun()
+---@param msec integer
+function c2.later(callback, msec) end
+
+return c2
+"#;
+
+/// Get the command a new plugin registers, based on its name
+fn command_name(name: &str) -> String {
+    let command: String = name
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+
+    if command.is_empty() {
+        "hello".to_string()
+    } else {
+        command
+    }
+}
+
+#[derive(Serialize)]
+struct PermissionInfo<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+}
+
+/// Contents of the `info.json` of a new plugin, in the order the fields are written
+#[derive(Serialize)]
+struct PluginInfo<'a> {
+    name: &'a str,
+    description: &'a str,
+    authors: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<&'a str>,
+    tags: &'a [String],
+    version: &'a str,
+    licence: &'a str,
+    permissions: Vec<PermissionInfo<'a>>,
+}
+
+/// Create the `info.json` of a new plugin
+///
+/// ### Arguments
+/// * `plugin` metadata the plugin starts with
+fn create_plugin_info(plugin: &Plugin) -> String {
+    let info = PluginInfo {
+        name: plugin.name.as_deref().unwrap_or(&plugin.folder),
+        description: plugin.description.as_deref().unwrap_or_default(),
+        authors: &plugin.authors,
+        homepage: plugin.homepage.as_deref(),
+        tags: &plugin.tags,
+        version: plugin.version.as_deref().unwrap_or(NEW_PLUGIN_VERSION),
+        licence: plugin.licence.as_deref().unwrap_or_default(),
+        permissions: plugin
+            .permissions
+            .iter()
+            .map(|p| PermissionInfo { type_: &p.type_ })
+            .collect(),
+    };
+
+    serde_json::to_string_pretty(&info).unwrap_or_default()
+}
+
+/// Get the files of a new plugin with their contents
+///
+/// ### Arguments
+/// * `plugin` metadata the plugin starts with
+pub fn create_plugin_files(plugin: &Plugin) -> Vec<(String, String)> {
+    let name = plugin.name.clone().unwrap_or(plugin.folder.clone());
+    let description = plugin.description.clone().unwrap_or_default();
+    let command = command_name(&plugin.folder);
+
+    let info = create_plugin_info(plugin);
+
+    let init_lua = format!(
+        r#"-- {name}
+
+c2.register_command("/{command}", function(ctx)
+    ctx.channel:add_system_message("Hello from {name}!")
+end)
+"#
+    );
+
+    let mut readme = format!("# {name}\n\n");
+    if !description.is_empty() {
+        readme.push_str(&format!("{description}\n\n"));
+    }
+    readme.push_str(&format!(
+        "## Usage\n\n`/{command}` shows a greeting in the current channel.\n\n\
+         ## Development\n\nCheck the plugin with `cpm validate` and try it with \
+         `cpm test --run /{command}`.\n"
+    ));
+    if let Some(licence) = plugin.licence.as_ref().filter(|l| !l.is_empty()) {
+        readme.push_str(&format!("\n## Licence\n\n{licence}\n"));
+    }
+
+    vec![
+        ("info.json".to_string(), format!("{info}\n")),
+        ("init.lua".to_string(), init_lua),
+        ("README.md".to_string(), readme),
+        (".gitignore".to_string(), GITIGNORE.to_string()),
+        (".luarc.json".to_string(), LUARC_JSON.to_string()),
+        (format!("{META_FOLDER}/c2.lua"), C2_META.to_string()),
+    ]
+}
+
+/// Write the files of a new plugin into an empty folder
+///
+/// ### Arguments
+/// * `plugin_path` path to the new plugin folder
+/// * `files` paths of files in the plugin with their contents
+pub fn write_plugin_files(plugin_path: &Path, files: &[(String, String)]) -> Result<(), String> {
+    for (file, contents) in files {
+        let path = plugin_path.join(file);
+        let err_str = format!("There was an error writing {}", path.to_string_lossy());
+
+        fs::create_dir_all(path.parent().unwrap()).or(Err(err_str.clone()))?;
+        fs::write(&path, contents).or(Err(err_str))?;
+    }
+
+    Ok(())
+}
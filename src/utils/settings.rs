@@ -0,0 +1,42 @@
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// Read Chatterino's settings.json, `None` if Chatterino hasn't written one yet
+///
+/// ### Arguments
+/// * `chatterino_path` path to Chatterino folder
+pub fn read_chatterino_settings(chatterino_path: &Path) -> Result<Option<Value>, String> {
+    let settings_path = chatterino_path.join("Settings").join("settings.json");
+    if !settings_path.is_file() {
+        return Ok(None);
+    }
+
+    let buf = fs::read_to_string(settings_path)
+        .or(Err("There was an error reading the Chatterino settings"))?;
+    let json =
+        serde_json::from_str(&buf).or(Err("There was an error parsing the Chatterino settings"))?;
+
+    Ok(Some(json))
+}
+
+/// Whether plugin support is turned on in Chatterino's settings, it is off by default
+pub fn is_plugin_support_enabled(settings: &Value) -> bool {
+    settings
+        .pointer("/plugins/supportEnabled")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Get the folder names of the plugins enabled in Chatterino's settings
+pub fn get_enabled_plugins(settings: &Value) -> Vec<String> {
+    settings
+        .pointer("/plugins/enabledPlugins")
+        .and_then(|v| v.as_array())
+        .map(|plugins| {
+            plugins
+                .iter()
+                .filter_map(|p| p.as_str().map(|p| p.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
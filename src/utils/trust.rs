@@ -0,0 +1,376 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use minisign_verify::{Error as MinisignError, PublicKey, Signature};
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+use tabled::{builder::Builder, settings::Style};
+use toml::{Table, Value};
+
+use super::{
+    config::{get_config_value, read_config, set_config_value},
+    progress::print_line,
+};
+
+/// Config key of the signature policy
+pub const SIGNATURE_POLICY_KEY: &str = "signatures";
+
+/// Extension of the minisign signature uploaded next to a release archive
+pub const SIGNATURE_EXTENSION: &str = "minisig";
+
+/// What to do with a plugin archive that isn't signed by a trusted key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// Install it with a warning
+    Warn,
+    /// Refuse to install it
+    Require,
+}
+
+/// Parse a signature policy as set in the config
+pub fn parse_signature_policy(value: &str) -> Result<SignaturePolicy, String> {
+    match value {
+        "warn" => Ok(SignaturePolicy::Warn),
+        "require" => Ok(SignaturePolicy::Require),
+        _ => Err(format!(
+            "Unknown signature policy '{value}', use warn or require"
+        )),
+    }
+}
+
+/// Get the signature policy from the config, `warn` if it isn't set
+pub fn get_signature_policy(config: &Table) -> Result<SignaturePolicy, String> {
+    match get_config_value(config, SIGNATURE_POLICY_KEY).and_then(|v| v.as_str()) {
+        Some(value) => parse_signature_policy(value),
+        None => Ok(SignaturePolicy::Warn),
+    }
+}
+
+/// A minisign public key trusted to sign plugin archives
+#[derive(Debug, Clone)]
+pub struct TrustedKey {
+    /// Key ID as minisign shows it
+    pub id: String,
+    /// The key in base64, as on the second line of a `minisign.pub`
+    pub key: String,
+    /// `owner/repo` of the repositories the key is trusted for, all if empty
+    pub repos: Vec<String>,
+}
+
+impl TrustedKey {
+    /// Parse a minisign public key
+    ///
+    /// ### Arguments
+    /// * `key` the key in base64, the contents of a `minisign.pub` or a path to one
+    pub fn parse(key: &str) -> Result<Self, String> {
+        let contents = if Path::new(key).is_file() {
+            fs::read_to_string(key).or(Err(format!("There was an error reading {key}")))?
+        } else {
+            key.to_string()
+        };
+
+        // a minisign.pub has an untrusted comment above the key
+        let key = contents
+            .lines()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty() && !l.starts_with("untrusted comment:"))
+            .ok_or("The public key is empty")?;
+        PublicKey::from_base64(key).map_err(|e| format!("Invalid public key: {e}"))?;
+
+        // the key ID is stored little-endian after the 2 byte algorithm
+        let bin = STANDARD
+            .decode(key)
+            .or(Err("Invalid public key: not base64"))?;
+        let id = u64::from_le_bytes(bin[2..10].try_into().unwrap());
+
+        Ok(TrustedKey {
+            id: format!("{id:016X}"),
+            key: key.to_string(),
+            repos: Vec::new(),
+        })
+    }
+
+    /// Whether the key is trusted to sign archives of a repository
+    pub fn is_trusted_for(&self, owner: &str, repo: &str) -> bool {
+        let name = format!("{owner}/{repo}");
+        self.repos.is_empty() || self.repos.iter().any(|r| r.eq_ignore_ascii_case(&name))
+    }
+}
+
+/// Whether a repository is given as `owner/repo`
+pub fn is_valid_repo(repo: &str) -> bool {
+    repo.split_once('/')
+        .is_some_and(|(owner, name)| !owner.is_empty() && !name.is_empty() && !name.contains('/'))
+}
+
+/// Get the trusted keys from the config, sorted by key ID
+///
+/// A key with a `for` that isn't a list of `owner/repo` is skipped with a warning, so a broken
+/// scope never trusts a key for all repositories.
+pub fn get_trusted_keys(config: &Table) -> Vec<TrustedKey> {
+    let Some(keys) = get_config_value(config, "trust").and_then(|v| v.as_table()) else {
+        return Vec::new();
+    };
+
+    // the config table is sorted by key, so are the keys
+    keys.iter()
+        .filter_map(|(id, entry)| {
+            let key = entry.get("key")?.as_str()?;
+            let repos = match entry.get("for") {
+                None => Vec::new(),
+                Some(value) => {
+                    let repos: Option<Vec<String>> = value.as_array().and_then(|repos| {
+                        repos
+                            .iter()
+                            .map(|r| r.as_str().filter(|r| is_valid_repo(r)).map(String::from))
+                            .collect()
+                    });
+                    let Some(repos) = repos else {
+                        print_line(&format!(
+                            "Warning: ignoring trusted key {id}, 'trust.{id}.for' is not a list \
+                             of owner/repo, remove it with `cpm trust remove {id}`"
+                        ));
+                        return None;
+                    };
+                    repos
+                }
+            };
+
+            Some(TrustedKey {
+                id: id.clone(),
+                key: key.to_string(),
+                repos,
+            })
+        })
+        .collect()
+}
+
+/// Store a trusted key in the config
+///
+/// ### Arguments
+/// * `config` config to store the key in
+/// * `key` key to trust
+pub fn set_trusted_key(config: &mut Table, key: &TrustedKey) -> Result<(), String> {
+    set_config_value(
+        config,
+        &format!("trust.{}.key", key.id),
+        Value::String(key.key.clone()),
+    )?;
+    set_config_value(
+        config,
+        &format!("trust.{}.for", key.id),
+        Value::Array(key.repos.iter().map(|r| Value::String(r.clone())).collect()),
+    )?;
+
+    Ok(())
+}
+
+/// Verify a minisign signature with the keys that could have made it
+///
+/// Returns the ID of the key that made the signature, or `None` if none of the keys did.
+///
+/// ### Arguments
+/// * `path` signed archive
+/// * `signature` contents of the `.minisig` file
+/// * `keys` keys trusted to sign the archive
+/// * `name` name of the archive, for error messages
+pub fn verify_signature(
+    path: &Path,
+    signature: &str,
+    keys: &[TrustedKey],
+    name: &str,
+) -> Result<Option<String>, String> {
+    let signature = Signature::decode(signature)
+        .map_err(|e| format!("{name}.{SIGNATURE_EXTENSION} is not a valid signature: {e}"))?;
+    let read_err_str = format!("There was an error reading {name}");
+
+    for key in keys {
+        let Ok(public_key) = PublicKey::from_base64(&key.key) else {
+            continue;
+        };
+
+        // current signatures are of a hash, so the archive can be read in chunks
+        let result = match public_key.verify_stream(&signature) {
+            Ok(mut verifier) => {
+                let mut file = File::open(path).or(Err(read_err_str.clone()))?;
+                let mut buf = [0; 64 * 1024];
+                loop {
+                    let n = file.read(&mut buf).or(Err(read_err_str.clone()))?;
+                    if n == 0 {
+                        break;
+                    }
+                    verifier.update(&buf[..n]);
+                }
+                verifier.finalize()
+            }
+            Err(MinisignError::UnsupportedLegacyMode) => {
+                let contents = fs::read(path).or(Err(read_err_str.clone()))?;
+                public_key.verify(&contents, &signature, true)
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(_) => return Ok(Some(key.id.clone())),
+            // signed by another key
+            Err(MinisignError::UnexpectedKeyId) => continue,
+            Err(e) => {
+                return Err(format!(
+                    "{name} does not match its signature by key {}: {e}",
+                    key.id
+                ))
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Report a plugin archive that isn't signed by a trusted key, following the signature policy
+fn handle_unsigned(policy: SignaturePolicy, problem: &str) -> Result<(), String> {
+    match policy {
+        SignaturePolicy::Require => Err(format!(
+            "{problem}, and the '{SIGNATURE_POLICY_KEY}' setting requires signatures"
+        )),
+        SignaturePolicy::Warn => {
+            print_line(&format!("Warning: {problem}"));
+            Ok(())
+        }
+    }
+}
+
+/// Check the signature of a release archive before it is extracted
+///
+/// A signature that doesn't match is always an error, a missing or untrusted one follows the
+/// signature policy.
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `path` downloaded archive
+/// * `signature` contents of the `.minisig` next to the archive, if the release has one
+/// * `name` name of the archive
+pub fn check_release_signature(
+    owner: &str,
+    repo: &str,
+    path: &Path,
+    signature: Option<&str>,
+    name: &str,
+) -> Result<(), String> {
+    let config = read_config()?;
+    let policy = get_signature_policy(&config)?;
+    let keys: Vec<TrustedKey> = get_trusted_keys(&config)
+        .into_iter()
+        .filter(|k| k.is_trusted_for(owner, repo))
+        .collect();
+
+    let Some(signature) = signature else {
+        return handle_unsigned(policy, &format!("{name} is not signed"));
+    };
+
+    match verify_signature(path, signature, &keys, name)? {
+        Some(id) => {
+            print_line(&format!("Verified the signature of {name} by key {id}"));
+            Ok(())
+        }
+        None => handle_unsigned(
+            policy,
+            &format!("{name} is not signed by a key trusted for {owner}/{repo}"),
+        ),
+    }
+}
+
+/// Check that a plugin may be installed from a repository tarball, which can't be signed
+///
+/// Only warns when a key is trusted for the repository, as its author then signs releases.
+///
+/// ### Arguments
+/// * `owner` repository owner
+/// * `repo` repository name
+/// * `folder` plugin folder name
+pub fn check_tarball_signature(owner: &str, repo: &str, folder: &str) -> Result<(), String> {
+    let config = read_config()?;
+    let policy = get_signature_policy(&config)?;
+    let has_repo_key = get_trusted_keys(&config)
+        .iter()
+        .any(|k| !k.repos.is_empty() && k.is_trusted_for(owner, repo));
+
+    if policy == SignaturePolicy::Warn && !has_repo_key {
+        return Ok(());
+    }
+
+    handle_unsigned(
+        policy,
+        &format!(
+            "{folder} is installed from a repository tarball, which can't be signed, \
+             use --release to install a signed release archive"
+        ),
+    )
+}
+
+pub fn print_trusted_keys(keys: &[TrustedKey]) {
+    let mut builder = Builder::default();
+    builder.push_record(["Key ID", "Trusted For", "Public Key"]);
+
+    for key in keys {
+        builder.push_record([
+            key.id.clone(),
+            if key.repos.is_empty() {
+                "All repositories".to_string()
+            } else {
+                key.repos.join(", ")
+            },
+            key.key.clone(),
+        ]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(config: &str) -> Vec<TrustedKey> {
+        get_trusted_keys(&toml::from_str(config).unwrap())
+    }
+
+    #[test]
+    fn scoped_and_global_keys() {
+        let keys = keys(
+            r#"
+            [trust.AAAA]
+            key = "a"
+            for = ["owner/repo"]
+
+            [trust.BBBB]
+            key = "b"
+            "#,
+        );
+        assert_eq!(keys.len(), 2);
+
+        assert_eq!(keys[0].id, "AAAA");
+        assert_eq!(keys[0].repos, ["owner/repo"]);
+        assert!(keys[0].is_trusted_for("Owner", "Repo"));
+        assert!(!keys[0].is_trusted_for("owner", "other"));
+
+        assert!(keys[1].repos.is_empty());
+        assert!(keys[1].is_trusted_for("anyone", "anything"));
+    }
+
+    #[test]
+    fn malformed_scope_is_skipped() {
+        for scope in [
+            r#""owner/repo""#,
+            "[1]",
+            r#"["repo"]"#,
+            r#"["a/b", "/c"]"#,
+            "{}",
+        ] {
+            let keys = keys(&format!("[trust.AAAA]\nkey = \"a\"\nfor = {scope}"));
+            assert!(keys.is_empty(), "for = {scope}");
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use std::{collections::BTreeMap, fs::File, io, path::Path};
+use tabled::{builder::Builder, settings::Style};
+
+use super::{common::list_files, data::is_plugin_data_path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Modified,
+    Missing,
+    Extra,
+}
+
+/// Get the sha256 of a file as hex
+pub fn hash_file(path: &Path) -> Result<String, String> {
+    let err_str = format!("There was an error reading {}", path.to_string_lossy());
+
+    let mut file = File::open(path).or(Err(err_str.clone()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).or(Err(err_str))?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hash every file of an installed plugin, except its data folder
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder in `Plugins/`
+pub fn hash_plugin_files(plugin_path: &Path) -> Result<BTreeMap<String, String>, String> {
+    let mut hashes = BTreeMap::new();
+
+    for (path, _) in list_files(plugin_path) {
+        if is_plugin_data_path(&path) {
+            continue;
+        }
+        let hash = hash_file(&plugin_path.join(&path))?;
+        hashes.insert(path, hash);
+    }
+
+    Ok(hashes)
+}
+
+/// Compare the files of an installed plugin against the hashes recorded when it was installed
+///
+/// ### Arguments
+/// * `plugin_path` path to the plugin folder in `Plugins/`
+/// * `recorded` file hashes recorded at install time
+pub fn verify_plugin_files(
+    plugin_path: &Path,
+    recorded: &BTreeMap<String, String>,
+) -> Result<Vec<(String, FileStatus)>, String> {
+    let current = hash_plugin_files(plugin_path)?;
+    let mut changes = Vec::new();
+
+    for (path, hash) in recorded {
+        match current.get(path) {
+            Some(current_hash) if current_hash == hash => {}
+            Some(_) => changes.push((path.clone(), FileStatus::Modified)),
+            None => changes.push((path.clone(), FileStatus::Missing)),
+        }
+    }
+    for path in current.keys() {
+        if !recorded.contains_key(path) {
+            changes.push((path.clone(), FileStatus::Extra));
+        }
+    }
+
+    changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(changes)
+}
+
+pub fn print_file_changes(changes: &[(String, FileStatus)]) {
+    let mut builder = Builder::default();
+    builder.push_record(["File", "Status"]);
+
+    for (path, status) in changes {
+        let status = match status {
+            FileStatus::Modified => "Modified",
+            FileStatus::Missing => "Missing",
+            FileStatus::Extra => "Extra",
+        };
+        builder.push_record([path.as_str(), status]);
+    }
+
+    let table = builder.build().with(Style::rounded()).to_string();
+    println!("{table}");
+}
+
+/// Format a unified diff of a file between the original and the installed version
+///
+/// Returns `None` if the file is the same in both.
+///
+/// ### Arguments
+/// * `path` path of the file inside the plugin folder
+/// * `original` contents in the original archive, if it was there
+/// * `installed` contents in the plugin folder, if it is there
+pub fn format_file_diff(
+    path: &str,
+    original: Option<&[u8]>,
+    installed: Option<&[u8]>,
+) -> Option<String> {
+    if original == installed {
+        return None;
+    }
+
+    let old_header = original.map_or("/dev/null".to_string(), |_| format!("a/{path}"));
+    let new_header = installed.map_or("/dev/null".to_string(), |_| format!("b/{path}"));
+
+    let (Ok(old), Ok(new)) = (
+        std::str::from_utf8(original.unwrap_or_default()),
+        std::str::from_utf8(installed.unwrap_or_default()),
+    ) else {
+        return Some(format!(
+            "Binary files {old_header} and {new_header} differ\n"
+        ));
+    };
+
+    let diff = TextDiff::from_lines(old, new);
+    Some(
+        diff.unified_diff()
+            .header(&old_header, &new_header)
+            .to_string(),
+    )
+}